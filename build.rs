@@ -0,0 +1,169 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind};
+use regex_syntax::ParserBuilder;
+
+const GENERAL_CATEGORIES: &[(&str, &[&str])] = &[
+    ("Letter", &["L"]),
+    ("Cased_Letter", &["LC"]),
+    ("Uppercase_Letter", &["Lu"]),
+    ("Lowercase_Letter", &["Ll"]),
+    ("Titlecase_Letter", &["Lt"]),
+    ("Modifier_Letter", &["Lm"]),
+    ("Other_Letter", &["Lo"]),
+    ("Mark", &["M", "Combining_Mark"]),
+    ("Nonspacing_Mark", &["Mn"]),
+    ("Spacing_Mark", &["Mc"]),
+    ("Enclosing_Mark", &["Me"]),
+    ("Number", &["N"]),
+    ("Decimal_Number", &["Nd", "digit"]),
+    ("Letter_Number", &["Nl"]),
+    ("Other_Number", &["No"]),
+    ("Punctuation", &["P", "punct"]),
+    ("Connector_Punctuation", &["Pc"]),
+    ("Dash_Punctuation", &["Pd"]),
+    ("Open_Punctuation", &["Ps"]),
+    ("Close_Punctuation", &["Pe"]),
+    ("Initial_Punctuation", &["Pi"]),
+    ("Final_Punctuation", &["Pf"]),
+    ("Other_Punctuation", &["Po"]),
+    ("Symbol", &["S"]),
+    ("Math_Symbol", &["Sm"]),
+    ("Currency_Symbol", &["Sc"]),
+    ("Modifier_Symbol", &["Sk"]),
+    ("Other_Symbol", &["So"]),
+    ("Separator", &["Z"]),
+    ("Space_Separator", &["Zs"]),
+    ("Line_Separator", &["Zl"]),
+    ("Paragraph_Separator", &["Zp"]),
+    ("Other", &["C"]),
+    ("Control", &["Cc", "cntrl"]),
+    ("Format", &["Cf"]),
+    ("Private_Use", &["Co"]),
+    ("Unassigned", &["Cn"]),
+];
+
+const SCRIPTS: &[&str] = &[
+    "Adlam", "Ahom", "Anatolian_Hieroglyphs", "Arabic", "Armenian", "Avestan", "Balinese", "Bamum",
+    "Bassa_Vah", "Batak", "Bengali", "Bhaiksuki", "Bopomofo", "Brahmi", "Braille", "Buginese", "Buhid",
+    "Canadian_Aboriginal", "Carian", "Caucasian_Albanian", "Chakma", "Cham", "Cherokee", "Chorasmian",
+    "Common", "Coptic", "Cuneiform", "Cypriot", "Cypro_Minoan", "Cyrillic", "Deseret", "Devanagari",
+    "Dives_Akuru", "Dogra", "Duployan", "Egyptian_Hieroglyphs", "Elbasan", "Elymaic", "Ethiopic",
+    "Georgian", "Glagolitic", "Gothic", "Grantha", "Greek", "Gujarati", "Gunjala_Gondi", "Gurmukhi", "Han",
+    "Hangul", "Hanifi_Rohingya", "Hanunoo", "Hatran", "Hebrew", "Hiragana", "Imperial_Aramaic",
+    "Inherited", "Inscriptional_Pahlavi", "Inscriptional_Parthian", "Javanese", "Kaithi", "Kannada",
+    "Katakana", "Kawi", "Kayah_Li", "Kharoshthi", "Khitan_Small_Script", "Khmer", "Khojki", "Khudawadi",
+    "Lao", "Latin", "Lepcha", "Limbu", "Linear_A", "Linear_B", "Lisu", "Lycian", "Lydian", "Mahajani",
+    "Makasar", "Malayalam", "Mandaic", "Manichaean", "Marchen", "Masaram_Gondi", "Medefaidrin",
+    "Meetei_Mayek", "Mende_Kikakui", "Meroitic_Cursive", "Meroitic_Hieroglyphs", "Miao", "Modi",
+    "Mongolian", "Mro", "Multani", "Myanmar", "Nabataean", "Nag_Mundari", "Nandinagari", "New_Tai_Lue",
+    "Newa", "Nko", "Nushu", "Nyiakeng_Puachue_Hmong", "Ogham", "Ol_Chiki", "Old_Hungarian", "Old_Italic",
+    "Old_North_Arabian", "Old_Permic", "Old_Persian", "Old_Sogdian", "Old_South_Arabian", "Old_Turkic",
+    "Old_Uyghur", "Oriya", "Osage", "Osmanya", "Pahawh_Hmong", "Palmyrene", "Pau_Cin_Hau", "Phags_Pa",
+    "Phoenician", "Psalter_Pahlavi", "Rejang", "Runic", "Samaritan", "Saurashtra", "Sharada", "Shavian",
+    "Siddham", "SignWriting", "Sinhala", "Sogdian", "Sora_Sompeng", "Soyombo", "Sundanese", "Syloti_Nagri",
+    "Syriac", "Tagalog", "Tagbanwa", "Tai_Le", "Tai_Tham", "Tai_Viet", "Takri", "Tamil", "Tangsa",
+    "Tangut", "Telugu", "Thaana", "Thai", "Tibetan", "Tifinagh", "Tirhuta", "Toto", "Ugaritic", "Vai",
+    "Vithkuqi", "Wancho", "Warang_Citi", "Yezidi", "Yi", "Zanabazar_Square",
+];
+
+const BINARY_PROPERTIES: &[&str] = &[
+    "Alphabetic", "ASCII_Hex_Digit", "Bidi_Control", "Cased", "Case_Ignorable", "Dash",
+    "Default_Ignorable_Code_Point", "Diacritic", "Emoji", "Emoji_Component", "Emoji_Modifier",
+    "Emoji_Modifier_Base", "Emoji_Presentation", "Extended_Pictographic", "Extender", "Hex_Digit",
+    "Hyphen", "ID_Continue", "ID_Start", "Ideographic", "Join_Control", "Lowercase", "Math",
+    "Noncharacter_Code_Point", "Pattern_Syntax", "Pattern_White_Space", "Quotation_Mark",
+    "Regional_Indicator", "Sentence_Terminal", "Terminal_Punctuation", "Unified_Ideograph", "Uppercase",
+    "Variation_Selector", "White_Space", "XID_Continue", "XID_Start", "Any", "ASCII", "Assigned",
+];
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn ranges(expression: &str) -> Vec<(char, char)> {
+    let hir = ParserBuilder::new()
+        .build()
+        .parse(expression)
+        .unwrap_or_else(|error| panic!("{}: {}", expression, error));
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => class.ranges().iter().map(|r| (r.start(), r.end())).collect(),
+        HirKind::Literal(literal) => std::str::from_utf8(&literal.0).unwrap().chars().map(|c| (c, c)).collect(),
+        kind => panic!("{} is not a Unicode class: {:?}", expression, kind),
+    }
+}
+
+/// Every character with simple case variants, paired with those variants.
+fn case_folding() -> String {
+    let mut output = String::from("pub(crate) static CASE_FOLDING: &[(char, &[char])] = &[\n");
+    for c in (0..=char::MAX as u32).filter_map(char::from_u32) {
+        let mut class = ClassUnicode::new([ClassUnicodeRange::new(c, c)]);
+        class.case_fold_simple();
+        let variants: Vec<char> = class
+            .ranges()
+            .iter()
+            .flat_map(|r| r.start()..=r.end())
+            .filter(|&variant| variant != c)
+            .collect();
+        if !variants.is_empty() {
+            writeln!(output, "    ({:?}, &{:?}),", c, variants).unwrap();
+        }
+    }
+    output.push_str("];\n");
+    output
+}
+
+#[derive(Default)]
+struct Tables {
+    source: String,
+    names: Vec<(String, &'static str, usize)>,
+    count: usize,
+}
+
+impl Tables {
+    fn add(&mut self, kind: &'static str, name: &str, aliases: &[&str], expression: &str) {
+        let index = self.count;
+        self.count += 1;
+        write!(self.source, "const TABLE_{}: &[(char, char)] = &[", index).unwrap();
+        for (start, end) in ranges(expression) {
+            write!(self.source, "({:?}, {:?}),", start, end).unwrap();
+        }
+        self.source.push_str("];\n");
+        for alias in std::iter::once(&name).chain(aliases) {
+            self.names.push((normalize(alias), kind, index));
+        }
+    }
+}
+
+fn main() {
+    let mut tables = Tables::default();
+    for (name, aliases) in GENERAL_CATEGORIES {
+        tables.add("GeneralCategory", name, aliases, &format!("\\p{{gc={}}}", name));
+    }
+    for name in SCRIPTS {
+        tables.add("Script", name, &[], &format!("\\p{{sc={}}}", name));
+    }
+    for name in BINARY_PROPERTIES {
+        tables.add("Binary", name, &[], &format!("\\p{{{}}}", name));
+    }
+
+    tables.names.sort();
+    tables.names.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+    let mut output = String::from("pub(crate) static PROPERTIES: &[Property] = &[\n");
+    for (name, kind, index) in &tables.names {
+        writeln!(output, "    ({:?}, PropertyKind::{}, TABLE_{}),", name, kind, index).unwrap();
+    }
+    output.push_str("];\n");
+    output.push_str(&tables.source);
+    output.push_str(&case_folding());
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("unicode_tables.rs");
+    fs::write(path, output).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,309 @@
+use crate::error::MatchError;
+use crate::patterns::{self, Pattern, Slots};
+
+#[derive(Clone, Copy, Debug)]
+enum Job<'p> {
+    Match(&'p Pattern),
+    Repeat {
+        pattern: &'p Pattern,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        count: usize,
+        iteration_start: Option<usize>,
+    },
+    CloseGroup(usize, usize),
+    Cut(usize),
+    AssertPosition(usize),
+}
+
+/// The most jobs one search may run by default before giving up, so that patterns which
+/// backtrack exponentially or quadratically fail with an error instead of stalling.
+pub(crate) const DEFAULT_STEP_LIMIT: usize = 50_000_000;
+
+/// A change to undo when backtracking to an earlier choice point.
+enum Undo<'p> {
+    Pushed,
+    Popped(Job<'p>),
+    Slot(usize, Option<(usize, usize)>),
+}
+
+/// A saved state, kept as the length of the undo trail at the time it was saved plus the
+/// jobs to push when resuming from it, so saving never copies the job stack or slots.
+struct ChoicePoint<'p> {
+    trail_length: usize,
+    position: usize,
+    alternative: [Option<Job<'p>>; 2],
+}
+
+struct Backtracker<'p, 'h> {
+    haystack: &'h str,
+    jobs: Vec<Job<'p>>,
+    position: usize,
+    slots: Slots,
+    choices: Vec<ChoicePoint<'p>>,
+    trail: Vec<Undo<'p>>,
+    budget: usize,
+    limit: usize,
+}
+
+impl<'p, 'h> Backtracker<'p, 'h> {
+    fn new(haystack: &'h str, budget: usize, limit: usize) -> Self {
+        Backtracker { haystack, jobs: vec![], position: 0, slots: vec![], choices: vec![], trail: vec![], budget, limit }
+    }
+
+    /// Runs `jobs` at exactly `start`, exploring alternatives and quantifier counts in
+    /// priority order with an explicit stack of choice points rather than native recursion.
+    /// On success, `slots` holds the group spans of the first successful path. The buffers
+    /// are reused from one call to the next, so a search allocates them only once.
+    fn match_at(&mut self, jobs: &[Job<'p>], start: usize, slots: &mut Slots) -> Result<Option<usize>, MatchError> {
+        self.jobs.clear();
+        self.jobs.extend_from_slice(jobs);
+        self.choices.clear();
+        self.trail.clear();
+        self.position = start;
+        self.slots.clone_from(slots);
+        let end = self.run();
+        if let Ok(Some(_)) = end {
+            slots.clone_from(&self.slots);
+        }
+        end
+    }
+
+    /// Runs the body of a lookaround in a backtracker of its own, so only the nesting of
+    /// lookarounds uses the native stack. It draws on the same step budget.
+    fn look_around(&mut self, jobs: &[Job<'p>], start: usize, slots: &mut Slots) -> Result<Option<usize>, MatchError> {
+        let mut inner = Backtracker::new(self.haystack, self.budget, self.limit);
+        let end = inner.match_at(jobs, start, slots);
+        self.budget = inner.budget;
+        end
+    }
+
+    fn record(&mut self, undo: Undo<'p>) {
+        if self.choices.is_empty() {
+            self.trail.clear();
+        } else {
+            self.trail.push(undo);
+        }
+    }
+
+    fn push_job(&mut self, job: Job<'p>) {
+        self.jobs.push(job);
+        self.record(Undo::Pushed);
+    }
+
+    fn pop_job(&mut self) -> Option<Job<'p>> {
+        let job = self.jobs.pop()?;
+        self.record(Undo::Popped(job));
+        Some(job)
+    }
+
+    fn set_slot(&mut self, index: usize, span: Option<(usize, usize)>) {
+        let previous = std::mem::replace(&mut self.slots[index], span);
+        self.record(Undo::Slot(index, previous));
+    }
+
+    fn set_slots(&mut self, slots: Slots) {
+        for (index, span) in slots.into_iter().enumerate() {
+            if self.slots[index] != span {
+                self.set_slot(index, span);
+            }
+        }
+    }
+
+    /// Saves the current state; resuming from it pushes `alternative`, at most two jobs.
+    fn save(&mut self, alternative: &[Job<'p>]) {
+        let mut jobs = [None, None];
+        for (slot, job) in jobs.iter_mut().zip(alternative) {
+            *slot = Some(*job);
+        }
+        self.choices.push(ChoicePoint { trail_length: self.trail.len(), position: self.position, alternative: jobs });
+    }
+
+    fn restore(&mut self) -> bool {
+        let Some(choice) = self.choices.pop() else {
+            return false;
+        };
+        while self.trail.len() > choice.trail_length {
+            match self.trail.pop().unwrap() {
+                Undo::Pushed => {
+                    self.jobs.pop();
+                },
+                Undo::Popped(job) => self.jobs.push(job),
+                Undo::Slot(index, span) => self.slots[index] = span,
+            }
+        }
+        self.position = choice.position;
+        for job in choice.alternative.into_iter().flatten() {
+            self.push_job(job);
+        }
+        true
+    }
+
+    fn step(&mut self, job: Job<'p>) -> bool {
+        match job {
+            Job::Match(pattern) => self.step_pattern(pattern),
+            Job::Repeat { pattern, min, max, greedy, count, iteration_start } => {
+                let was_empty = iteration_start == Some(self.position);
+                if max == Some(count) || (was_empty && count >= min) {
+                    return true;
+                }
+                let iteration = [
+                    Job::Repeat { pattern, min, max, greedy, count: count + 1, iteration_start: Some(self.position) },
+                    Job::Match(pattern),
+                ];
+                if count < min || greedy {
+                    if count >= min {
+                        self.save(&[]);
+                    }
+                    iteration.into_iter().for_each(|job| self.push_job(job));
+                } else {
+                    self.save(&iteration);
+                }
+                true
+            },
+            Job::CloseGroup(index, start) => {
+                self.set_slot(index, Some((start, self.position)));
+                true
+            },
+            Job::Cut(height) => {
+                self.choices.truncate(height);
+                true
+            },
+            Job::AssertPosition(position) => self.position == position,
+        }
+    }
+
+    fn step_pattern(&mut self, pattern: &'p Pattern) -> bool {
+        match pattern {
+            Pattern::Empty => true,
+            Pattern::SingleCharacter(_) | Pattern::Any(_, _) | Pattern::Wildcard => {
+                match self.haystack[self.position..].chars().next() {
+                    Some(c) if pattern.matches_char(c) => {
+                        self.position += c.len_utf8();
+                        true
+                    },
+                    _ => false,
+                }
+            },
+            Pattern::Sequence(patterns) => {
+                patterns.iter().rev().for_each(|p| self.push_job(Job::Match(p)));
+                true
+            },
+            Pattern::Choice(alternatives) => {
+                for alternative in alternatives[1..].iter().rev() {
+                    self.save(&[Job::Match(alternative)]);
+                }
+                self.push_job(Job::Match(&alternatives[0]));
+                true
+            },
+            Pattern::Group(p, index) => {
+                self.push_job(Job::CloseGroup(*index, self.position));
+                self.push_job(Job::Match(p));
+                true
+            },
+            Pattern::Repeat(p, min, max, greedy) => {
+                self.push_job(Job::Repeat { pattern: p, min: *min, max: *max, greedy: *greedy, count: 0, iteration_start: None });
+                true
+            },
+            Pattern::Atomic(p) => {
+                self.push_job(Job::Cut(self.choices.len()));
+                self.push_job(Job::Match(p));
+                true
+            },
+            Pattern::LookAhead(p, is_negative) => {
+                let mut slots = self.slots.clone();
+                let found = match self.look_around(&[Job::Match(p)], self.position, &mut slots) {
+                    Ok(end) => end.is_some(),
+                    Err(_) => return false,
+                };
+                if found && !is_negative {
+                    self.set_slots(slots);
+                }
+                found != *is_negative
+            },
+            Pattern::LookBehind(p, is_negative, min, max) => {
+                let starts = std::iter::once(self.position)
+                    .chain(self.haystack[..self.position].char_indices().rev().map(|(i, _)| i))
+                    .skip(*min)
+                    .take((max - min).saturating_add(1));
+                for start in starts {
+                    let mut slots = self.slots.clone();
+                    let jobs = [Job::AssertPosition(self.position), Job::Match(p)];
+                    match self.look_around(&jobs, start, &mut slots) {
+                        Ok(Some(_)) => {
+                            if !is_negative {
+                                self.set_slots(slots);
+                            }
+                            return !is_negative;
+                        },
+                        Ok(None) => {},
+                        Err(_) => return false,
+                    }
+                }
+                *is_negative
+            },
+            Pattern::Start => self.position == 0,
+            Pattern::End => self.position == self.haystack.len(),
+            Pattern::LineStart => self.haystack[..self.position].ends_with('\n') || self.position == 0,
+            Pattern::LineEnd => self.haystack[self.position..].starts_with('\n') || self.position == self.haystack.len(),
+            Pattern::EndBeforeNewline => patterns::is_end_before_newline(self.haystack, self.position),
+            Pattern::Boundary(boundary, unicode) => boundary.matches(*unicode, self.haystack, self.position),
+            Pattern::BackReference(index, folding) => {
+                let Some(Some((start, end))) = self.slots.get(*index).copied() else {
+                    return false;
+                };
+                let mut remaining = self.haystack[self.position..].chars();
+                for expected in self.haystack[start..end].chars() {
+                    match remaining.next() {
+                        Some(c) if folding.matches(expected, c) => self.position += c.len_utf8(),
+                        _ => return false,
+                    }
+                }
+                true
+            },
+        }
+    }
+
+    /// Runs jobs until none are left or every choice point has failed. A lookaround
+    /// that runs out of budget leaves it at zero, which ends the search with an error.
+    fn run(&mut self) -> Result<Option<usize>, MatchError> {
+        let exceeded = Err(MatchError::StepLimitExceeded { limit: self.limit });
+        loop {
+            if self.budget == 0 {
+                return exceeded;
+            }
+            self.budget -= 1;
+            let Some(job) = self.pop_job() else {
+                return Ok(Some(self.position));
+            };
+            if !self.step(job) && !self.restore() {
+                return if self.budget == 0 { exceeded } else { Ok(None) };
+            }
+        }
+    }
+}
+
+/// Finds the leftmost match starting at or after `from`, sharing one budget of `limit`
+/// steps across all start positions.
+pub(crate) fn search(
+    pattern: &Pattern,
+    group_count: usize,
+    haystack: &str,
+    from: usize,
+    limit: usize,
+) -> Result<Option<Slots>, MatchError> {
+    let starts = haystack[from..]
+        .char_indices()
+        .map(|(i, _)| from + i)
+        .chain(std::iter::once(haystack.len()));
+    let mut backtracker = Backtracker::new(haystack, limit, limit);
+    for start in starts {
+        let mut slots: Slots = vec![None; group_count + 1];
+        if let Some(end) = backtracker.match_at(&[Job::Match(pattern)], start, &mut slots)? {
+            slots[0] = Some((start, end));
+            return Ok(Some(slots));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,275 @@
+use std::sync::OnceLock;
+
+use crate::unicode::{self, CaseFolding};
+
+/// A set of characters kept as sorted, non-overlapping intervals so lookups are a
+/// binary search. Bracket expressions, shorthand classes and Unicode properties all
+/// resolve to one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassSet {
+    ranges: Vec<(char, char)>,
+}
+
+const EQUIVALENCES: &[&str] = &[
+    "aàáâãäåāăą", "AÀÁÂÃÄÅĀĂĄ", "cçćĉċč", "CÇĆĈĊČ", "dďđ", "DĎĐ", "eèéêëēĕėęě", "EÈÉÊËĒĔĖĘĚ",
+    "gĝğġģ", "GĜĞĠĢ", "hĥħ", "HĤĦ", "iìíîïĩīĭįı", "IÌÍÎÏĨĪĬĮİ", "jĵ", "JĴ", "kķ", "KĶ",
+    "lĺļľŀł", "LĹĻĽĿŁ", "nñńņňŉ", "NÑŃŅŇ", "oòóôõöøōŏő", "OÒÓÔÕÖØŌŎŐ", "rŕŗř", "RŔŖŘ",
+    "sśŝşš", "SŚŜŞŠ", "tţťŧ", "TŢŤŦ", "uùúûüũūŭůűų", "UÙÚÛÜŨŪŬŮŰŲ", "wŵ", "WŴ", "yýÿŷ", "YÝŶŸ",
+    "zźżž", "ZŹŻŽ",
+];
+
+const COLLATING_NAMES: &[(&str, char)] = &[
+    ("NUL", '\0'), ("tab", '\t'), ("newline", '\n'), ("carriage-return", '\r'), ("space", ' '),
+    ("exclamation-mark", '!'), ("quotation-mark", '"'), ("number-sign", '#'), ("dollar-sign", '$'),
+    ("percent-sign", '%'), ("ampersand", '&'), ("apostrophe", '\''), ("left-parenthesis", '('),
+    ("right-parenthesis", ')'), ("asterisk", '*'), ("plus-sign", '+'), ("comma", ','), ("hyphen", '-'),
+    ("hyphen-minus", '-'), ("period", '.'), ("full-stop", '.'), ("slash", '/'), ("solidus", '/'),
+    ("colon", ':'), ("semicolon", ';'), ("less-than-sign", '<'), ("equals-sign", '='),
+    ("greater-than-sign", '>'), ("question-mark", '?'), ("commercial-at", '@'),
+    ("left-square-bracket", '['), ("backslash", '\\'), ("reverse-solidus", '\\'),
+    ("right-square-bracket", ']'), ("circumflex", '^'), ("circumflex-accent", '^'), ("underscore", '_'),
+    ("low-line", '_'), ("grave-accent", '`'), ("left-brace", '{'), ("left-curly-bracket", '{'),
+    ("vertical-line", '|'), ("right-brace", '}'), ("right-curly-bracket", '}'), ("tilde", '~'),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shorthand {
+    Digit,
+    Word,
+    Space,
+    HorizontalSpace,
+    VerticalSpace,
+}
+
+/// Every backslash class letter, with the class it names and whether it is negated.
+const SHORTHANDS: &[(char, Shorthand, bool)] = &[
+    ('d', Shorthand::Digit, false),
+    ('D', Shorthand::Digit, true),
+    ('w', Shorthand::Word, false),
+    ('W', Shorthand::Word, true),
+    ('s', Shorthand::Space, false),
+    ('S', Shorthand::Space, true),
+    ('h', Shorthand::HorizontalSpace, false),
+    ('H', Shorthand::HorizontalSpace, true),
+    ('v', Shorthand::VerticalSpace, false),
+    ('V', Shorthand::VerticalSpace, true),
+];
+
+pub fn shorthand(letter: char) -> Option<(Shorthand, bool)> {
+    SHORTHANDS
+        .iter()
+        .find(|(l, _, _)| *l == letter)
+        .map(|(_, class, is_negative)| (*class, *is_negative))
+}
+
+impl Shorthand {
+    /// The characters of the class, from the Unicode tables or restricted to ASCII.
+    pub fn class_set(self, unicode: bool) -> ClassSet {
+        if !unicode {
+            return ClassSet::from_ranges(match self {
+                Shorthand::Digit => &[('0', '9')],
+                Shorthand::Word => &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+                Shorthand::Space => &[('\t', '\r'), (' ', ' ')],
+                Shorthand::HorizontalSpace => &[('\t', '\t'), (' ', ' ')],
+                Shorthand::VerticalSpace => &[('\n', '\r')],
+            });
+        }
+        let properties: &[&str] = match self {
+            Shorthand::Digit => &["Nd"],
+            Shorthand::Word => &["Alphabetic", "M", "Nd", "Pc", "Join_Control"],
+            Shorthand::Space => &["White_Space"],
+            Shorthand::HorizontalSpace => {
+                return ClassSet::from_ranges(&[
+                    ('\t', '\t'), (' ', ' '), ('\u{A0}', '\u{A0}'), ('\u{1680}', '\u{1680}'),
+                    ('\u{180E}', '\u{180E}'), ('\u{2000}', '\u{200A}'), ('\u{202F}', '\u{202F}'),
+                    ('\u{205F}', '\u{205F}'), ('\u{3000}', '\u{3000}'),
+                ])
+            },
+            Shorthand::VerticalSpace => {
+                return ClassSet::from_ranges(&[('\n', '\r'), ('\u{85}', '\u{85}'), ('\u{2028}', '\u{2029}')])
+            },
+        };
+        property_union(properties)
+    }
+}
+
+/// The union of the named Unicode property tables.
+fn property_union(properties: &[&str]) -> ClassSet {
+    let mut set = ClassSet::default();
+    for property in properties {
+        set.union(&unicode::property_class(property).unwrap());
+    }
+    set.canonicalize();
+    set
+}
+
+/// Whether `c` is in `\w`, which decides where word boundaries fall. The Unicode
+/// class is built once on first use.
+pub fn is_word_char(c: char, unicode: bool) -> bool {
+    static WORD: OnceLock<ClassSet> = OnceLock::new();
+    if unicode {
+        WORD.get_or_init(|| Shorthand::Word.class_set(true)).contains(c)
+    } else {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+}
+
+/// The ranges of a POSIX `[:name:]` class, following the C locale, or in Unicode mode
+/// the properties UTS #18 gives for it. `xdigit` stays ASCII in both.
+pub fn posix_class(name: &str, unicode: bool) -> Option<ClassSet> {
+    if unicode {
+        let set = match name {
+            "alpha" => property_union(&["Alphabetic"]),
+            "digit" => Shorthand::Digit.class_set(true),
+            "alnum" => property_union(&["Alphabetic", "Nd"]),
+            "word" => Shorthand::Word.class_set(true),
+            "upper" => property_union(&["Uppercase"]),
+            "lower" => property_union(&["Lowercase"]),
+            "space" => Shorthand::Space.class_set(true),
+            "blank" => Shorthand::HorizontalSpace.class_set(true),
+            "punct" => property_union(&["P", "S"]),
+            "graph" => property_union(&["White_Space", "Cc", "Cn"]).complement(),
+            "print" => {
+                let mut set = property_union(&["White_Space", "Cc", "Cn"]).complement();
+                set.union(&Shorthand::HorizontalSpace.class_set(true));
+                set.canonicalize();
+                set
+            },
+            "cntrl" => property_union(&["Cc"]),
+            "xdigit" => ClassSet::from_ranges(&[('0', '9'), ('A', 'F'), ('a', 'f')]),
+            _ => return None,
+        };
+        return Some(set);
+    }
+    let ranges: &[(char, char)] = match name {
+        "alpha" => &[('A', 'Z'), ('a', 'z')],
+        "digit" => &[('0', '9')],
+        "alnum" => &[('0', '9'), ('A', 'Z'), ('a', 'z')],
+        "word" => &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        "upper" => &[('A', 'Z')],
+        "lower" => &[('a', 'z')],
+        "space" => &[('\t', '\r'), (' ', ' ')],
+        "blank" => &[('\t', '\t'), (' ', ' ')],
+        "punct" => &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+        "print" => &[(' ', '~')],
+        "graph" => &[('!', '~')],
+        "cntrl" => &[('\0', '\x1F'), ('\x7F', '\x7F')],
+        "xdigit" => &[('0', '9'), ('A', 'F'), ('a', 'f')],
+        _ => return None,
+    };
+    Some(ClassSet::from_ranges(ranges))
+}
+
+/// The characters sharing a base letter with `c` for a `[=c=]` equivalence class.
+pub fn equivalence_class(c: char) -> ClassSet {
+    let mut set = ClassSet::default();
+    match EQUIVALENCES.iter().find(|group| group.contains(c)) {
+        Some(group) => group.chars().for_each(|c| set.add_char(c)),
+        None => set.add_char(c),
+    }
+    set.canonicalize();
+    set
+}
+
+/// Resolves the contents of a `[.name.]` collating element, which is either a single
+/// character or one of the POSIX portable character names.
+pub fn collating_element(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => COLLATING_NAMES.iter().find(|(n, _)| *n == name).map(|(_, c)| *c),
+    }
+}
+
+impl ClassSet {
+    pub fn from_ranges(ranges: &[(char, char)]) -> Self {
+        let mut set = ClassSet { ranges: ranges.to_vec() };
+        set.canonicalize();
+        set
+    }
+
+    pub fn union(&mut self, other: &ClassSet) {
+        self.ranges.extend_from_slice(&other.ranges);
+    }
+
+    pub fn complement(&self) -> ClassSet {
+        let mut ranges = vec![];
+        let mut next = 0u32;
+        for &(start, end) in &self.ranges {
+            if (start as u32) > next {
+                push_scalar_range(&mut ranges, next, start as u32 - 1);
+            }
+            next = end as u32 + 1;
+        }
+        if next <= char::MAX as u32 {
+            push_scalar_range(&mut ranges, next, char::MAX as u32);
+        }
+        ClassSet { ranges }
+    }
+
+    pub fn add_char(&mut self, c: char) {
+        self.add_range(c, c);
+    }
+
+    pub fn add_range(&mut self, start: char, end: char) {
+        self.ranges.push((start, end));
+    }
+
+    /// Adds the case variants of every member, leaving the set canonical.
+    pub fn case_fold(&mut self, folding: CaseFolding) {
+        if folding == CaseFolding::Exact {
+            return;
+        }
+        let mut variants = vec![];
+        for &(c, others) in unicode::CASE_FOLDING {
+            if self.contains(c) {
+                variants.extend(others.iter().filter(|&&other| folding.matches(c, other)));
+            }
+        }
+        variants.into_iter().for_each(|c| self.add_char(c));
+        self.canonicalize();
+    }
+
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    pub fn canonicalize(&mut self) {
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(self.ranges.len());
+        for &(start, end) in &self.ranges {
+            match merged.last_mut() {
+                Some((_, last_end)) if (*last_end as u32).saturating_add(1) >= start as u32 => {
+                    *last_end = (*last_end).max(end);
+                },
+                _ => merged.push((start, end)),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self
+            .ranges
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    std::cmp::Ordering::Less
+                } else if start > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+fn push_scalar_range(ranges: &mut Vec<(char, char)>, start: u32, end: u32) {
+    const SURROGATES: (u32, u32) = (0xD800, 0xDFFF);
+    if start < SURROGATES.0 {
+        ranges.push((char::from_u32(start).unwrap(), char::from_u32(end.min(SURROGATES.0 - 1)).unwrap()));
+    }
+    if end > SURROGATES.1 {
+        ranges.push((char::from_u32(start.max(SURROGATES.1 + 1)).unwrap(), char::from_u32(end).unwrap()));
+    }
+}
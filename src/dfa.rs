@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::mem;
+
+use crate::nfa::{Inst, Program};
+
+const UNKNOWN: u32 = u32::MAX;
+const CACHE_CAPACITY: usize = 2 * 1024 * 1024;
+const MAX_RESETS_PER_SEARCH: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    kernel: Vec<usize>,
+    at_start: bool,
+}
+
+#[derive(Debug)]
+struct State {
+    key: Key,
+    char_pcs: Vec<usize>,
+    is_match: bool,
+    is_match_at_end: bool,
+    is_dead: bool,
+    ascii: [u32; 128],
+    other: HashMap<char, u32>,
+}
+
+impl State {
+    fn memory(&self) -> usize {
+        mem::size_of::<State>()
+            + (self.key.kernel.len() + self.char_pcs.len()) * mem::size_of::<usize>() * 2
+            + self.other.len() * (mem::size_of::<char>() + mem::size_of::<u32>()) * 2
+    }
+}
+
+/// Lazily built DFA states for one program. Each state is the set of NFA instructions
+/// reached after consuming some input, and transitions are filled in as they are first
+/// taken. The cache is flushed when it outgrows its memory budget.
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    states: Vec<State>,
+    ids: HashMap<Key, u32>,
+    memory: usize,
+}
+
+impl Cache {
+    fn clear(&mut self) {
+        self.states.clear();
+        self.ids.clear();
+        self.memory = 0;
+    }
+}
+
+struct LazyDfa<'p, 'c> {
+    program: &'p Program,
+    cache: &'c mut Cache,
+    resets: usize,
+}
+
+impl LazyDfa<'_, '_> {
+    fn closure(&self, kernel: &[usize], at_start: bool, at_end: bool) -> (Vec<usize>, bool) {
+        let mut seen = vec![false; self.program.insts.len()];
+        let mut stack: Vec<usize> = kernel.iter().rev().copied().collect();
+        let mut char_pcs = vec![];
+        let mut is_match = false;
+        while let Some(pc) = stack.pop() {
+            if mem::replace(&mut seen[pc], true) {
+                continue;
+            }
+            match self.program.insts[pc] {
+                Inst::Char(_) => char_pcs.push(pc),
+                Inst::Match => is_match = true,
+                Inst::Jump(target) => stack.push(target),
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                },
+                Inst::Save(_) => stack.push(pc + 1),
+                // Going on after an empty iteration reaches nothing that stopping misses.
+                Inst::EmptyCheck(_, exit) => {
+                    stack.push(exit);
+                    stack.push(pc + 1);
+                },
+                Inst::Start => {
+                    if at_start {
+                        stack.push(pc + 1);
+                    }
+                },
+                Inst::End => {
+                    if at_end {
+                        stack.push(pc + 1);
+                    }
+                },
+                // Declined by `is_match` before any state is built.
+                Inst::LineStart | Inst::LineEnd | Inst::EndBeforeNewline | Inst::Boundary(_, _) => {},
+            }
+        }
+        (char_pcs, is_match)
+    }
+
+    fn add_state(&mut self, key: Key) -> Option<u32> {
+        if let Some(&id) = self.cache.ids.get(&key) {
+            return Some(id);
+        }
+        if self.cache.memory > CACHE_CAPACITY {
+            self.resets += 1;
+            if self.resets > MAX_RESETS_PER_SEARCH {
+                return None;
+            }
+            self.cache.clear();
+        }
+        let (char_pcs, is_match) = self.closure(&key.kernel, key.at_start, false);
+        let (_, is_match_at_end) = self.closure(&key.kernel, key.at_start, true);
+        let is_dead = char_pcs.is_empty() && !is_match_at_end;
+        let state = State {
+            key: key.clone(),
+            char_pcs,
+            is_match,
+            is_match_at_end,
+            is_dead,
+            ascii: [UNKNOWN; 128],
+            other: HashMap::new(),
+        };
+        let id = self.cache.states.len() as u32;
+        self.cache.memory += state.memory();
+        self.cache.states.push(state);
+        self.cache.ids.insert(key, id);
+        Some(id)
+    }
+
+    fn cached_transition(&self, id: u32, c: char) -> u32 {
+        let state = &self.cache.states[id as usize];
+        if c.is_ascii() {
+            state.ascii[c as usize]
+        } else {
+            state.other.get(&c).copied().unwrap_or(UNKNOWN)
+        }
+    }
+
+    fn transition(&mut self, id: u32, c: char) -> Option<u32> {
+        let cached = self.cached_transition(id, c);
+        if cached != UNKNOWN {
+            return Some(cached);
+        }
+        let state = &self.cache.states[id as usize];
+        let mut kernel: Vec<usize> = state
+            .char_pcs
+            .iter()
+            .filter(|pc| matches!(&self.program.insts[**pc], Inst::Char(class) if self.program.classes[*class].matches_char(c)))
+            .map(|pc| pc + 1)
+            .collect();
+        kernel.push(0);
+        kernel.sort_unstable();
+        kernel.dedup();
+        let previous_key = state.key.clone();
+        let next = self.add_state(Key { kernel, at_start: false })?;
+        let id = match self.cache.ids.get(&previous_key) {
+            Some(&id) => id,
+            None => return Some(next),
+        };
+        let state = &mut self.cache.states[id as usize];
+        if c.is_ascii() {
+            state.ascii[c as usize] = next;
+        } else {
+            state.other.insert(c, next);
+            self.cache.memory += (mem::size_of::<char>() + mem::size_of::<u32>()) * 2;
+        }
+        Some(next)
+    }
+
+    fn is_match(&mut self, haystack: &str) -> Option<bool> {
+        let mut id = self.add_state(Key { kernel: vec![0], at_start: true })?;
+        for c in haystack.chars() {
+            let state = &self.cache.states[id as usize];
+            if state.is_match {
+                return Some(true);
+            }
+            if state.is_dead {
+                return Some(false);
+            }
+            id = self.transition(id, c)?;
+        }
+        Some(self.cache.states[id as usize].is_match_at_end)
+    }
+}
+
+/// Answers whether `program` matches anywhere in `haystack` without tracking spans.
+/// Returns `None` when the cache keeps overflowing or the program has assertions that
+/// depend on neighbouring characters; the caller should then use an engine that does
+/// not build states.
+pub(crate) fn is_match(program: &Program, cache: &mut Cache, haystack: &str) -> Option<bool> {
+    if program.has_context_assertions {
+        return None;
+    }
+    LazyDfa { program, cache, resets: 0 }.is_match(haystack)
+}
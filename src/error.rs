@@ -0,0 +1,85 @@
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum PatternError {
+    #[error("unclosed bracket expression")]
+    UnclosedBracket { offset: usize },
+    #[error("invalid range in bracket expression")]
+    InvalidRange { offset: usize },
+    #[error("unknown character class [:{name}:]")]
+    UnknownClass { offset: usize, name: String },
+    #[error("invalid collating element {name:?}")]
+    InvalidCollatingElement { offset: usize, name: String },
+    #[error("unknown Unicode property {name:?}")]
+    UnknownProperty { offset: usize, name: String },
+    #[error("unclosed group")]
+    UnclosedGroup { offset: usize },
+    #[error("unrecognized group syntax")]
+    InvalidGroup { offset: usize },
+    #[error("lookbehind must have a bounded length")]
+    UnboundedLookBehind { offset: usize },
+    #[error("invalid group name {name:?}")]
+    InvalidGroupName { offset: usize, name: String },
+    #[error("duplicate group name {name:?}")]
+    DuplicateGroupName { offset: usize, name: String },
+    #[error("no group named {name:?}")]
+    UnknownGroupName { offset: usize, name: String },
+    #[error("unknown flag '{flag}'")]
+    UnknownFlag { offset: usize, flag: char },
+    #[error("unmatched closing parenthesis")]
+    UnmatchedParenthesis { offset: usize },
+    #[error("invalid escape sequence")]
+    InvalidEscape { offset: usize },
+    #[error("trailing backslash")]
+    TrailingBackslash { offset: usize },
+    #[error("invalid repetition range")]
+    InvalidRepetitionRange { offset: usize },
+    #[error("repetition count exceeds {limit}")]
+    RepetitionTooLarge { offset: usize, limit: usize },
+    #[error("pattern compiles to more than {limit} instructions")]
+    PatternTooLarge { offset: usize, limit: usize },
+    #[error("quantifier follows another quantifier")]
+    RepeatedQuantifier { offset: usize },
+    #[error("invalid back reference to group {group}")]
+    InvalidBackReference { offset: usize, group: usize },
+    #[error("relative back reference -{distance} precedes the first group")]
+    InvalidRelativeBackReference { offset: usize, distance: usize },
+    #[error("malformed back reference")]
+    InvalidBackReferenceSyntax { offset: usize },
+}
+
+impl PatternError {
+    pub fn offset(&self) -> usize {
+        match self {
+            PatternError::UnclosedBracket { offset }
+            | PatternError::InvalidRange { offset }
+            | PatternError::UnknownClass { offset, .. }
+            | PatternError::InvalidCollatingElement { offset, .. }
+            | PatternError::UnknownProperty { offset, .. }
+            | PatternError::UnclosedGroup { offset }
+            | PatternError::InvalidGroup { offset }
+            | PatternError::UnboundedLookBehind { offset }
+            | PatternError::InvalidGroupName { offset, .. }
+            | PatternError::DuplicateGroupName { offset, .. }
+            | PatternError::UnknownGroupName { offset, .. }
+            | PatternError::UnknownFlag { offset, .. }
+            | PatternError::UnmatchedParenthesis { offset }
+            | PatternError::InvalidEscape { offset }
+            | PatternError::TrailingBackslash { offset }
+            | PatternError::InvalidRepetitionRange { offset }
+            | PatternError::RepetitionTooLarge { offset, .. }
+            | PatternError::PatternTooLarge { offset, .. }
+            | PatternError::RepeatedQuantifier { offset }
+            | PatternError::InvalidBackReference { offset, .. }
+            | PatternError::InvalidRelativeBackReference { offset, .. }
+            | PatternError::InvalidBackReferenceSyntax { offset } => *offset,
+        }
+    }
+}
+
+/// A search that gave up before finding an answer.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum MatchError {
+    #[error("backtracking exceeded {limit} steps")]
+    StepLimitExceeded { limit: usize },
+}
@@ -1 +1,10 @@
-pub mod patterns;
\ No newline at end of file
+mod backtrack;
+pub mod classes;
+mod dfa;
+pub mod error;
+mod nfa;
+pub mod parser;
+pub mod patterns;
+mod pikevm;
+pub mod regex;
+pub mod unicode;
@@ -1,30 +1,95 @@
 use std::env;
-use std::io;
+use std::io::{self, BufRead};
 use std::process;
-use codecrafters_grep::patterns::match_pattern;
+use codecrafters_grep::error::PatternError;
+use codecrafters_grep::regex::{Regex, RegexBuilder};
 
-// Usage: echo <input_text> | your_program.sh -E <pattern>
+fn report_pattern_error(pattern: &str, error: &PatternError) {
+    let column = pattern[..error.offset()].chars().count();
+    eprintln!("grep: {}", error);
+    eprintln!("  {}", pattern);
+    eprintln!("  {}^", " ".repeat(column));
+}
+
+/// Whether any record of `reader` matches. Records end with `separator`, which is a
+/// line feed normally and NUL with `-z`, so that a record can span several lines.
+fn any_line_matches(regex: &Regex, reader: impl BufRead, separator: u8) -> io::Result<bool> {
+    for line in reader.split(separator) {
+        let line = line?;
+        if regex.try_is_match(&String::from_utf8_lossy(&line)).map_err(io::Error::other)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Usage: echo <input_text> | your_program.sh -E [-i|--ignore-case] [--smart-case] [--ascii]
+//            [-z|--null-data] [--multiline] [--dot-all] [--] <pattern>
 fn main() {
-    if env::args().nth(1).unwrap() != "-E" {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) != Some("-E") {
         println!("Expected first argument to be '-E'");
         process::exit(1);
     }
 
-    let pattern = env::args().nth(2).unwrap();
-    let mut input_line = String::new();
+    let mut ascii = false;
+    let mut ignore_case = false;
+    let mut smart_case = false;
+    let mut separator = b'\n';
+    let mut multi_line = false;
+    let mut dot_all = false;
+    let mut index = 2;
+    while let Some(arg) = args.get(index).filter(|arg| arg.len() > 1 && arg.starts_with('-')) {
+        index += 1;
+        match arg.as_str() {
+            "--" => break,
+            "--ascii" => ascii = true,
+            "-i" | "--ignore-case" => ignore_case = true,
+            "--smart-case" => smart_case = true,
+            "-z" | "--null-data" => separator = b'\0',
+            "--multiline" => multi_line = true,
+            "--dot-all" => dot_all = true,
+            _ => {
+                eprintln!("grep: unrecognized option '{}'", arg);
+                process::exit(2)
+            }
+        }
+    }
+    let Some(pattern) = args.get(index) else {
+        println!("Expected a pattern after '-E'");
+        process::exit(1);
+    };
 
-    io::stdin().read_line(&mut input_line).unwrap();
+    let mut builder = RegexBuilder::new(pattern);
+    builder.unicode(!ascii);
+    builder.case_insensitive(ignore_case);
+    builder.smart_case(smart_case);
+    builder.multi_line(multi_line);
+    builder.dot_all(dot_all);
+    let regex = match builder.build() {
+        Ok(regex) => regex,
+        Err(error) => {
+            report_pattern_error(pattern, &error);
+            process::exit(2)
+        }
+    };
 
-    if match_pattern(&input_line, &pattern) {
-        process::exit(0)
-    } else {
-        process::exit(1)
+    match any_line_matches(&regex, io::stdin().lock(), separator) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("grep: (standard input): {}", error);
+            process::exit(2)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::any_line_matches;
+    use codecrafters_grep::error::{MatchError, PatternError};
     use codecrafters_grep::patterns::match_pattern;
+    use codecrafters_grep::regex::{Regex, RegexBuilder};
 
     #[test]
     fn test_single_char() {
@@ -71,17 +136,22 @@ mod tests {
     fn test_start_anchor() {
         assert!(match_pattern("log", "^log"));
         assert!(!match_pattern("slog", "^log"));
-        assert!(match_pattern("Hey ^log", " ^log"));
-        assert!(match_pattern("^log", "^^log"));
+        assert!(match_pattern("Hey ^log", " \\^log"));
+        assert!(!match_pattern("^log", "^^log"));
+        assert!(match_pattern("log", "^^log"));
         assert!(match_pattern("^log", "\\^log"));
+        assert!(match_pattern("x\nfoo", "(?m)(^foo|bar$)"));
+        assert!(match_pattern("a\nb", "(?m)a\n^b"));
     }
 
     #[test]
     fn test_end_anchor() {
         assert!(match_pattern("cat", "cat$"));
         assert!(!match_pattern("cats", "cat$"));
-        assert!(match_pattern("For $5 only!", "$\\d only!"));
-        assert!(match_pattern("For $5 only!", "$\\d only!$"));
+        assert!(!match_pattern("For $5 only!", "$\\d only!"));
+        assert!(match_pattern("For $5 only!", "\\$\\d only!$"));
+        assert!(match_pattern("a\nb", "(?m)a$\nb"));
+        assert!(!match_pattern("a\nb", "a$\nb"));
         assert!(match_pattern("For $5 only!", "[%?$]"));
     }
 
@@ -157,4 +227,672 @@ mod tests {
         assert!(match_pattern("cat and fish, cat with fish", "(c.t|d.g) and (f..h|b..d), \\1 with \\2"));
         assert!(!match_pattern("bat and fish, cat with fish", "(c.t|d.g) and (f..h|b..d), \\1 with \\2"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_nested_groups() {
+        assert!(match_pattern("bc", "((a|b)c|d)"));
+        assert!(match_pattern("d", "((a|b)c|d)"));
+        assert!(!match_pattern("cc", "((a|b)c|d)"));
+        assert!(match_pattern("x9y", "x([abc]|(\\d|z))y"));
+        assert!(match_pattern("abab ab", "((a)(b))+ \\1"));
+        assert!(match_pattern("ac and a", "((a|b)(c|d)) and \\2"));
+        assert!(!match_pattern("ac and c", "((a|b)(c|d)) and \\2"));
+    }
+
+    #[test]
+    fn test_pattern_errors() {
+        assert_eq!(Regex::new("a[bc").unwrap_err(), PatternError::UnclosedBracket { offset: 1 });
+        assert_eq!(Regex::new("x(a|b").unwrap_err(), PatternError::UnclosedGroup { offset: 1 });
+        assert_eq!(Regex::new("ab)").unwrap_err(), PatternError::UnmatchedParenthesis { offset: 2 });
+        assert_eq!(Regex::new("ab\\").unwrap_err(), PatternError::TrailingBackslash { offset: 2 });
+        assert_eq!(Regex::new("[a\\").unwrap_err(), PatternError::TrailingBackslash { offset: 2 });
+        assert_eq!(Regex::new("(a) \\2").unwrap_err(), PatternError::InvalidBackReference { offset: 4, group: 2 });
+        assert_eq!(Regex::new("é[").unwrap_err().offset(), 2);
+        assert!(Regex::new("(a)\\1").is_ok());
+    }
+
+    #[test]
+    fn test_reusable_regex() {
+        let regex = Regex::new("(\\w+)@(\\w+)").unwrap();
+        assert_eq!(regex.group_count(), 2);
+        assert!(regex.is_match("mail bob@example now"));
+        assert!(!regex.is_match("no address here"));
+        assert_eq!(regex.find("mail bob@example now").map(|m| m.range()), Some(5..16));
+        assert!(regex.captures("nothing").is_none());
+    }
+
+    #[test]
+    fn test_match_spans() {
+        let regex = Regex::new("(\\d+)-(x)?(\\d+)").unwrap();
+        let captures = regex.captures("call 555-1234 now").unwrap();
+        assert_eq!(captures.len(), 4);
+        assert_eq!(captures.get(0).map(|m| m.range()), Some(5..13));
+        assert_eq!(captures.get(0).map(|m| m.as_str()), Some("555-1234"));
+        assert_eq!(captures.get(1).map(|m| m.range()), Some(5..8));
+        assert_eq!(captures.get(2), None);
+        assert_eq!(captures.get(3).map(|m| m.as_str()), Some("1234"));
+        assert_eq!(captures.get(4), None);
+
+        let spans: Vec<(usize, usize)> = Regex::new("\\d+")
+            .unwrap()
+            .find_iter("a1 b22 c333")
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        assert_eq!(spans, vec![(1, 2), (4, 6), (8, 11)]);
+        assert_eq!(Regex::new("x?").unwrap().find_iter("ab").count(), 3);
+        assert_eq!(Regex::new("é").unwrap().find("café").map(|m| m.range()), Some(3..5));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        let corpus = [
+            ("a+ab", "aaab", Some(0..4)),
+            ("\\w+s", "cats", Some(0..4)),
+            ("ca?a", "ca", Some(0..2)),
+            ("(a|ab)c", "abc", Some(0..3)),
+            ("(a+)+b", "aaab", Some(0..4)),
+            ("x(\\d+)\\d", "x12345", Some(0..6)),
+            ("(\\w+) \\1", "hello llo", Some(2..9)),
+            ("\\d?\\d?\\d\\d", "123", Some(0..3)),
+            ("a?b+c", "bbbd", None),
+        ];
+        for (pattern, haystack, expected) in corpus {
+            let found = Regex::new(pattern).unwrap().find(haystack).map(|m| m.range());
+            assert_eq!(found, expected, "{} against {:?}", pattern, haystack);
+        }
+        let captures = Regex::new("(\\w+)(\\d)").unwrap().captures("abc123").unwrap();
+        assert_eq!(captures.get(1).map(|m| m.as_str()), Some("abc12"));
+        assert_eq!(captures.get(2).map(|m| m.as_str()), Some("3"));
+
+        let long_line = "a".repeat(200_000) + "x";
+        assert!(match_pattern(&long_line, "^.+x$"));
+        assert!(!match_pattern(&long_line, "^.+y"));
+    }
+
+    #[test]
+    fn test_backtrack_limit() {
+        let haystack = "a".repeat(3_000);
+        assert_eq!(Regex::new("(a)\\1.*y").unwrap().try_is_match(&haystack), Ok(false));
+        assert_eq!(Regex::new("(?=(a+))\\1$").unwrap().try_find(&haystack).unwrap().map(|m| m.len()), Some(3_000));
+
+        let exponential = RegexBuilder::new("((a|aa)+)+(?=)y").backtrack_limit(100_000).build().unwrap();
+        let exceeded = Err(MatchError::StepLimitExceeded { limit: 100_000 });
+        assert_eq!(exponential.try_is_match(&haystack[..40]), exceeded);
+        assert!(exponential.try_captures(&haystack[..40]).is_err());
+        assert_eq!(exponential.try_find("aay").unwrap().map(|m| m.range()), Some(0..3));
+
+        let input = haystack[..40].to_string() + "\n";
+        assert!(any_line_matches(&exponential, input.as_bytes(), b'\n').is_err());
+    }
+
+    #[test]
+    fn test_linear_time_engine() {
+        let haystack = "x".repeat(5_000);
+        assert!(!match_pattern(&haystack, "(x+x+)+y"));
+        assert!(!match_pattern(&haystack, "(x?)+(x?)+(x?)+y"));
+        assert!(match_pattern(&(haystack.clone() + "y"), "(x+x+)+y"));
+
+        let captures = Regex::new("((a|b)+)(c?)").unwrap().captures("zzabbac").unwrap();
+        let groups: Vec<Option<&str>> = captures.iter().map(|m| m.map(|m| m.as_str())).collect();
+        assert_eq!(groups, vec![Some("abbac"), Some("abba"), Some("a"), Some("c")]);
+    }
+
+    #[test]
+    fn test_lazy_dfa() {
+        let regex = Regex::new("^(\\d+|x)-[abc]+$").unwrap();
+        for line in ["12-abc", "x-a", "12-abd", "-abc", "1x-a", ""] {
+            assert_eq!(regex.is_match(line), regex.find(line).is_some(), "{:?}", line);
+        }
+
+        let mut seed: u32 = 7;
+        let haystack: String = (0..60_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                if (seed >> 16) & 1 == 0 { 'a' } else { 'b' }
+            })
+            .collect();
+        let pattern = format!("a{}c", "(a|b)".repeat(14));
+        let regex = Regex::new(&pattern).unwrap();
+        assert!(!regex.is_match(&haystack));
+        assert!(regex.is_match(&(haystack.clone() + &"a".repeat(15) + "c")));
+        assert!(Regex::new(&format!("a{}b", "(a|b)".repeat(14))).unwrap().is_match(&haystack));
+    }
+
+    #[test]
+    fn test_star_and_counters() {
+        assert!(match_pattern("ct", "ca*t"));
+        assert!(match_pattern("caaat", "ca*t"));
+        assert!(match_pattern("abab!", "^(ab)*!"));
+        assert!(match_pattern("!", "^[ab]*!$"));
+        assert!(match_pattern("caaat", "ca{3}t"));
+        assert!(!match_pattern("caat", "ca{3}t"));
+        assert!(match_pattern("caaaaat", "ca{3,}t"));
+        assert!(!match_pattern("caat", "ca{3,}t"));
+        assert!(match_pattern("caat", "ca{2,3}t"));
+        assert!(!match_pattern("caaaat", "ca{2,3}t"));
+        assert!(match_pattern("ct", "ca{,2}t"));
+        assert!(match_pattern("12-34-56", "^(\\d{2}-?){3}$"));
+        assert!(match_pattern("a{b", "a{b"));
+        assert!(match_pattern("a{,}", "a{,}"));
+        assert!(match_pattern("aab", "(a|b){2,}(a)?b"));
+        assert!(match_pattern("aaaa", "^(a{2})\\1$"));
+        assert!(!match_pattern("aaa", "^(a{2})\\1$"));
+        assert!(match_pattern("xyz-xyz", "^(\\w{2,5})-\\1$"));
+        assert_eq!(Regex::new("^(a*)+$").unwrap().find("aaa").map(|m| m.range()), Some(0..3));
+        let found = Regex::new("(\\d{1,3})(\\d*)").unwrap().captures("12345").unwrap();
+        assert_eq!(found.get(1).map(|m| m.as_str()), Some("123"));
+        assert_eq!(found.get(2).map(|m| m.as_str()), Some("45"));
+        assert_eq!(Regex::new("a{3,2}").unwrap_err(), PatternError::InvalidRepetitionRange { offset: 1 });
+        assert_eq!(Regex::new("a{1001}").unwrap_err(), PatternError::RepetitionTooLarge { offset: 1, limit: 1000 });
+        assert_eq!(Regex::new("a**").unwrap_err(), PatternError::RepeatedQuantifier { offset: 2 });
+        assert_eq!(Regex::new("a{2}{3}").unwrap_err(), PatternError::RepeatedQuantifier { offset: 4 });
+        assert_eq!(Regex::new("a+?*").unwrap_err(), PatternError::RepeatedQuantifier { offset: 3 });
+        assert_eq!(Regex::new("(?x)a* +").unwrap_err(), PatternError::RepeatedQuantifier { offset: 7 });
+        assert!(match_pattern("aa{x", "^a{2}{x$"));
+
+        // Copies of a class share it, so these stay on the linear-time engine.
+        let classes = Regex::new("(\\w{1000}){50}").unwrap();
+        assert!(!classes.is_match("word"));
+        let nested = Regex::new("(?:\\w{1,130})+y").unwrap();
+        assert!(!nested.is_match(&"a".repeat(30)));
+        let too_large = Regex::new("^((a{1000}){1000}){1000}").unwrap_err();
+        assert_eq!(too_large, PatternError::PatternTooLarge { offset: 0, limit: 100_000 });
+    }
+
+    #[test]
+    fn test_engines_agree_on_empty_iterations() {
+        let corpus = [
+            ("(|a)*", "aa"),
+            ("(|a)*b", "aab"),
+            ("(a*?)*", "aa"),
+            ("(a*)*b", "aab"),
+            ("(a|)*?b", "aab"),
+            ("(a?)*(a)", "aaa"),
+            ("((a)|b?)*c", "abac"),
+            ("(a*)+", "b"),
+            ("(a{0,2})*", "aaaaa"),
+            ("(|a){0,3}b", "aab"),
+            ("(a|b?){2,}?c", "abc"),
+        ];
+        for (pattern, haystack) in corpus {
+            // The empty lookahead forces the backtracking engine.
+            let pike = Regex::new(pattern).unwrap();
+            let backtracking = Regex::new(&format!("(?:{})(?=)", pattern)).unwrap();
+            let spans = |regex: &Regex| {
+                let captures = regex.captures(haystack).unwrap();
+                captures.iter().map(|m| m.map(|m| m.range())).collect::<Vec<_>>()
+            };
+            assert_eq!(spans(&pike), spans(&backtracking), "{} against {:?}", pattern, haystack);
+        }
+    }
+
+    #[test]
+    fn test_lazy_and_possessive() {
+        let quoted = Regex::new("\"(.+?)\"").unwrap();
+        let captures = quoted.captures("say \"hi\" and \"bye\"").unwrap();
+        assert_eq!(captures.get(1).map(|m| m.as_str()), Some("hi"));
+        let greedy = Regex::new("\"(.+)\"").unwrap();
+        assert_eq!(greedy.captures("say \"hi\" and \"bye\"").unwrap().get(1).map(|m| m.as_str()), Some("hi\" and \"bye"));
+        let found = |pattern: &str, haystack: &str| Regex::new(pattern).unwrap().find(haystack).map(|m| m.range());
+        assert_eq!(found("a*?", "aaa"), Some(0..0));
+        assert_eq!(found("a+?", "aaa"), Some(0..1));
+        assert_eq!(found("ba??", "ba"), Some(0..1));
+        assert_eq!(found("a{2,4}?", "aaaa"), Some(0..2));
+        assert_eq!(found("<.*?>x", "<a><b>x"), Some(0..7));
+        assert_eq!(found("a*+b", "aaab"), Some(0..4));
+        assert!(!match_pattern("aaa", "a*+a"));
+        assert!(!match_pattern("aaa", "a++a"));
+        assert!(match_pattern("aaa", "a?+aa"));
+        assert!(!match_pattern("aaaa", "^a{2,3}+a{2}$"));
+        assert!(!match_pattern("abc", "(?>a|ab)c"));
+        assert!(match_pattern("abc", "(?>ab|a)c"));
+        assert!(!match_pattern("abab", "^(ab)*+\\1$"));
+        assert!(match_pattern("abab!ab", "(ab)*+!\\1"));
+        assert_eq!(Regex::new("(?<x)").unwrap_err(), PatternError::InvalidGroup { offset: 0 });
+    }
+
+    #[test]
+    fn test_bracket_ranges() {
+        assert!(match_pattern("x7", "^[a-z0-9]+$"));
+        assert!(!match_pattern("X7", "^[a-z0-9]+$"));
+        assert!(match_pattern("-", "[a-]"));
+        assert!(match_pattern("-", "[-a]"));
+        assert!(match_pattern("-", "[a\\-z]"));
+        assert!(!match_pattern("b", "[a\\-z]"));
+        assert!(match_pattern("]", "[]a]"));
+        assert!(match_pattern("b", "[^]a]"));
+        assert!(!match_pattern("]", "[^]a]"));
+        assert!(match_pattern("λ", "[α-ω]"));
+        assert!(match_pattern("😀", "[\u{1F600}-\u{1F64F}]"));
+        assert!(match_pattern("5", "[\\d-]"));
+        assert!(match_pattern("-", "[\\d-]"));
+        assert!(match_pattern("c", "[a-cx-z]"));
+        assert!(!match_pattern("m", "[a-cx-z]"));
+        assert!(match_pattern("7", "[0-37-9]"));
+        assert!(!match_pattern("5", "[0-37-9]"));
+        assert_eq!(Regex::new("[z-a]").unwrap_err(), PatternError::InvalidRange { offset: 1 });
+        assert_eq!(Regex::new("[a-\\d]").unwrap_err(), PatternError::InvalidRange { offset: 1 });
+        assert_eq!(Regex::new("[]").unwrap_err(), PatternError::UnclosedBracket { offset: 0 });
+    }
+
+    #[test]
+    fn test_posix_classes() {
+        assert!(match_pattern("abc", "^[[:alpha:]]+$"));
+        assert!(!match_pattern("ab1", "^[[:alpha:]]+$"));
+        assert!(match_pattern("42", "^[[:digit:]]+$"));
+        assert!(match_pattern("a b\tc", "a[[:space:]]b[[:blank:]]c"));
+        assert!(match_pattern("hi!", "[[:punct:]]"));
+        assert!(!match_pattern("hi", "[[:punct:]]"));
+        assert!(match_pattern("0xBEEF", "^0x[[:xdigit:]]+$"));
+        assert!(!match_pattern("0xBEEG", "^0x[[:xdigit:]]+$"));
+        assert!(match_pattern("A_1", "^[[:upper:]_[:digit:]]+$"));
+        assert!(match_pattern("x", "[^[:digit:][:space:]]"));
+        assert!(match_pattern("café", "caf[[=e=]]"));
+        assert!(match_pattern("cafe", "caf[[=é=]]"));
+        assert!(!match_pattern("cafE", "caf[[=e=]]"));
+        assert!(match_pattern("a-b", "a[[.-.]]b"));
+        assert!(match_pattern("a-b", "a[[.hyphen.]]b"));
+        assert!(match_pattern("c", "[[.a.]-[.c.]]"));
+        assert!(match_pattern(":", "[:]"));
+        assert!(matches!(Regex::new("[[:alpah:]]").unwrap_err(), PatternError::UnknownClass { offset: 1, .. }));
+        assert!(matches!(Regex::new("[[.foo.]]").unwrap_err(), PatternError::InvalidCollatingElement { offset: 1, .. }));
+        assert_eq!(Regex::new("[[:alpha:]").unwrap_err(), PatternError::UnclosedBracket { offset: 0 });
+
+        assert!(match_pattern("Ωμέγα", "^[[:alpha:]]+$"));
+        assert!(match_pattern("٤٢", "^[[:digit:]]+$"));
+        assert!(match_pattern("É", "^[[:upper:]]$"));
+        assert!(match_pattern("a\u{3000}b", "a[[:space:]]b"));
+        assert!(match_pattern("«»", "^[[:punct:]]+$"));
+        assert!(match_pattern("日本", "^[[:graph:]]+$"));
+        assert!(!match_pattern("\u{85}", "[[:print:]]"));
+        assert!(!match_pattern("٤", "[[:xdigit:]]"));
+        let ascii = RegexBuilder::new("^[[:alpha:]]+$").unicode(false).build().unwrap();
+        assert!(ascii.is_match("abc"));
+        assert!(!ascii.is_match("Ωμέγα"));
+        assert!(match_pattern("Ω", "(?-u)[^[:alpha:]]"));
+    }
+
+    #[test]
+    fn test_shorthand_classes() {
+        assert!(match_pattern("a b", "a\\sb"));
+        assert!(!match_pattern("a_b", "a\\sb"));
+        assert!(match_pattern("a_b", "a\\Sb"));
+        assert!(match_pattern("abc", "^\\D+$"));
+        assert!(!match_pattern("ab1", "^\\D+$"));
+        assert!(match_pattern("a-b", "a\\Wb"));
+        assert!(!match_pattern("a_b", "a\\Wb"));
+        assert!(match_pattern("a\tb", "a\\hb"));
+        assert!(match_pattern("a\u{A0}b", "a\\hb"));
+        assert!(!match_pattern("a\nb", "a\\hb"));
+        assert!(match_pattern("a\nb", "a\\vb"));
+        assert!(match_pattern("a\nb", "a\\Hb"));
+        assert!(match_pattern("a b", "a\\Vb"));
+        assert!(match_pattern(" 1", "^[\\s\\d]+$"));
+        assert!(!match_pattern(" x", "^[\\s\\d]+$"));
+        assert!(match_pattern("x", "^[\\D]$"));
+        assert!(!match_pattern("7", "[\\D]"));
+        assert!(match_pattern("7", "[^\\D]"));
+        assert!(match_pattern("q", "[^\\d\\s]"));
+    }
+
+    #[test]
+    fn test_unicode_properties() {
+        assert!(match_pattern("Ωmega", "^\\p{Greek}"));
+        assert!(!match_pattern("omega", "^\\p{Greek}"));
+        assert!(match_pattern("漢字", "^\\p{Han}+$"));
+        assert!(match_pattern("abc漢", "\\P{Han}"));
+        assert!(!match_pattern("漢字", "\\P{Han}"));
+        assert!(match_pattern("é", "^\\p{L}$"));
+        assert!(match_pattern("é", "^\\pL$"));
+        assert!(match_pattern("É", "^\\p{Lu}$"));
+        assert!(match_pattern("É", "^\\p{Uppercase_Letter}$"));
+        assert!(!match_pattern("é", "^\\p{Lu}$"));
+        assert!(match_pattern("٣", "^\\p{Nd}$"));
+        assert!(match_pattern("٣", "^\\p{gc=Decimal Number}$"));
+        assert!(match_pattern("я", "^\\p{sc=Cyrillic}$"));
+        assert!(match_pattern("я", "^\\p{Script:cyrillic}$"));
+        assert!(match_pattern("x", "^\\p{^Greek}$"));
+        assert!(!match_pattern("x", "^\\P{^Greek}$"));
+        assert!(match_pattern("😀", "^\\p{Emoji_Presentation}$"));
+        assert!(match_pattern("\u{2003}", "\\p{White_Space}"));
+        assert!(match_pattern("a", "^\\p{IsAlphabetic}$"));
+        assert!(match_pattern("αβ1", "^[\\p{Greek}\\d]+$"));
+        assert!(!match_pattern("αβx", "^[\\p{Greek}\\d]+$"));
+        assert!(match_pattern("x", "^[\\P{Greek}]$"));
+        assert!(matches!(Regex::new("\\p{Klingon}").unwrap_err(), PatternError::UnknownProperty { offset: 0, .. }));
+        assert!(matches!(Regex::new("a\\p{Greek").unwrap_err(), PatternError::UnknownProperty { offset: 1, .. }));
+    }
+
+    #[test]
+    fn test_unicode_and_ascii_modes() {
+        assert!(match_pattern("٣", "^\\d$"));
+        assert!(match_pattern("ø", "^\\w$"));
+        assert!(match_pattern("\u{2003}", "^\\s$"));
+        assert!(!match_pattern("٣", "^(?-u)\\d$"));
+        assert!(!match_pattern("ø", "^(?-u)\\w$"));
+        assert!(!match_pattern("\u{2003}", "^(?-u)\\s$"));
+        assert!(match_pattern("ø", "^(?-u)\\W$"));
+        assert!(match_pattern("٣", "^(?-u)[^\\d]$"));
+        assert!(match_pattern("øø", "^(?-u:\\W)\\w$"));
+        assert!(!match_pattern("øø", "^(?-u:\\W)(?-u:\\w)$"));
+        assert!(match_pattern("øø", "^((?-u)\\W)\\w$"));
+
+        let ascii = RegexBuilder::new("^\\w+$").unicode(false).build().unwrap();
+        assert!(ascii.is_match("abc_1"));
+        assert!(!ascii.is_match("héllo"));
+        let overridden = RegexBuilder::new("^(?u)\\w+$").unicode(false).build().unwrap();
+        assert!(overridden.is_match("héllo"));
+        assert_eq!(Regex::new("(?-q)").unwrap_err(), PatternError::UnknownFlag { offset: 3, flag: 'q' });
+    }
+
+    #[test]
+    fn test_multibyte_offsets() {
+        let regex = Regex::new("世界").unwrap();
+        let found = regex.find("你好世界!").unwrap();
+        assert_eq!((found.start(), found.end(), found.as_str()), (6, 12, "世界"));
+
+        let emoji = Regex::new("(😀+)(.)").unwrap();
+        let captures = emoji.captures("a😀😀b").unwrap();
+        assert_eq!(captures.get(1).unwrap().range(), 1..9);
+        assert_eq!(captures.get(2).unwrap().as_str(), "b");
+        let backtracked = Regex::new("(.)\\1").unwrap();
+        assert_eq!(backtracked.find("x😀😀y").unwrap().range(), 1..9);
+
+        let combining = "e\u{301}";
+        assert!(!match_pattern(combining, "^.$"));
+        assert!(match_pattern(combining, "^e\\p{M}$"));
+        assert_eq!(Regex::new("\\p{M}").unwrap().find(combining).unwrap().range(), 1..3);
+
+        let empties: Vec<_> = Regex::new("x*").unwrap().find_iter("漢😀").map(|m| m.start()).collect();
+        assert_eq!(empties, vec![0, 3, 7]);
+        let words: Vec<_> = Regex::new("\\w+").unwrap().find_iter("日本 ñu 😀 z").map(|m| m.as_str()).collect();
+        assert_eq!(words, vec!["日本", "ñu", "z"]);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(!match_pattern("HELLO", "hello"));
+        assert!(match_pattern("HELLO", "(?i)hello"));
+        assert!(match_pattern("HeLLo", "^(?i)hello$"));
+        assert!(match_pattern("xY", "^x(?i:y)$"));
+        assert!(!match_pattern("XY", "^x(?i:y)$"));
+        assert!(match_pattern("aB", "^(?i)a(?-i)B$"));
+        assert!(!match_pattern("ab", "^(?i)a(?-i)B$"));
+        assert!(match_pattern("DEAD", "^(?i)[a-f]+$"));
+        assert!(!match_pattern("G", "^(?i)[a-f]$"));
+        assert!(!match_pattern("A", "^(?i)[^a]$"));
+        assert!(match_pattern("ΣΑΣ", "^(?i)σας$"));
+        assert!(match_pattern("\u{212A}", "^(?i)k$"));
+        assert!(!match_pattern("\u{212A}", "^(?i-u)k$"));
+        assert!(match_pattern("ǅ", "^(?i)ǆ$"));
+        assert!(match_pattern("abcABC", "^(?i)(abc)\\1$"));
+        assert!(match_pattern("straßeSTRAẞE", "^(?i)(straße)\\1$"));
+        assert!(!match_pattern("abcABC", "^(abc)\\1$"));
+        assert!(match_pattern("É", "^(?i)\\p{Ll}$"));
+
+        let regex = RegexBuilder::new("^hello$").case_insensitive(true).build().unwrap();
+        assert!(regex.is_match("Hello"));
+        let smart = |pattern: &str| RegexBuilder::new(pattern).smart_case(true).build().unwrap();
+        assert!(smart("hello \\W\\p{Greek}\\PL").is_match("HELLO !α1"));
+        assert!(!smart("Hello").is_match("hello"));
+        assert!(!smart("\\p{L}É").is_match("aé"));
+        assert!(smart("caf\\u00E9 \\x{6a}\\cA").is_match("CAFÉ J\u{1}"));
+        assert!(!smart("\\x{4A}").is_match("j"));
+        assert!(!smart("[A-C]").is_match("b"));
+        assert!(!smart("\\QX\\E").is_match("x"));
+    }
+
+    #[test]
+    fn test_inline_flags() {
+        let multi_line = Regex::new("(?m)^b$").unwrap();
+        assert_eq!(multi_line.find("a\nb\nc").unwrap().range(), 2..3);
+        assert!(!match_pattern("a\nb\nc", "^b$"));
+        assert!(match_pattern("a\nb", "(?m)^a$"));
+        assert!(match_pattern("a\n", "(?m)^a$"));
+        let backtracked = Regex::new("(?m)^(b)\\1$").unwrap();
+        assert_eq!(backtracked.find("a\nbb\nc").unwrap().range(), 2..4);
+
+        assert!(!match_pattern("a\nb", "a.b"));
+        assert!(match_pattern("a\nb", "(?s)a.b"));
+        assert!(match_pattern("a\nb", "a(?s:.)b"));
+        assert!(!match_pattern("a\nb\n", "a(?s:.)b."));
+
+        let documented = "(?x)
+            ^ (\\d{4})   # year
+            - (\\d{2})   # month
+            - (\\d{2})   # day
+            $";
+        let regex = Regex::new(documented).unwrap();
+        let captures = regex.captures("2024-05-17").unwrap();
+        assert_eq!(captures.get(2).unwrap().as_str(), "05");
+        assert!(!regex.is_match("2024 05 17"));
+        assert!(match_pattern("a b", "^(?x) a \\  b $"));
+        assert!(match_pattern("a b", "^(?x:a) b$"));
+        assert!(match_pattern("ab", "^(?x: a (?-x)b)$"));
+        assert!(match_pattern("A\nB", "(?ims)^a.b$"));
+        assert!(match_pattern("x#", "(?x)[#] # comment"));
+    }
+
+    #[test]
+    fn test_non_capturing_groups() {
+        let regex = Regex::new("(?:a|b)+(c)").unwrap();
+        assert_eq!(regex.group_count(), 1);
+        let captures = regex.captures("abac").unwrap();
+        assert_eq!(captures.get(0).unwrap().as_str(), "abac");
+        assert_eq!(captures.get(1).unwrap().as_str(), "c");
+        assert!(!match_pattern("abab", "^(?:(a)b)+\\1$"));
+        assert!(match_pattern("ababa", "^(?:(a)b)+\\1$"));
+
+        let nested = Regex::new("((a)(?:(b)|(c)))(d)").unwrap();
+        let captures = nested.captures("acd").unwrap();
+        let groups: Vec<_> = captures.iter().map(|m| m.map(|m| m.as_str())).collect();
+        assert_eq!(groups, vec![Some("acd"), Some("ac"), Some("a"), None, Some("c"), Some("d")]);
+        assert_eq!(captures.len(), 6);
+
+        let backtracked = Regex::new("(?>(x)|(y))(z)\\2?").unwrap();
+        let captures = backtracked.captures("xz").unwrap();
+        assert_eq!(captures.get(1).unwrap().as_str(), "x");
+        assert!(captures.get(2).is_none());
+        assert_eq!(captures.get(3).unwrap().as_str(), "z");
+    }
+
+    #[test]
+    fn test_named_groups() {
+        let regex = Regex::new("(?P<year>\\d{4})-(?<month>\\d{2})-(\\d{2})").unwrap();
+        let captures = regex.captures("on 2024-05-17").unwrap();
+        assert_eq!(captures.name("year").unwrap().as_str(), "2024");
+        assert_eq!(captures.name("month").unwrap().as_str(), "05");
+        assert_eq!(captures.get(2).unwrap().as_str(), "05");
+        assert_eq!(captures.get(3).unwrap().as_str(), "17");
+        assert!(captures.name("day").is_none());
+
+        assert!(match_pattern("abab", "^(?<pair>ab)\\k<pair>$"));
+        assert!(match_pattern("abab", "^(?P<pair>ab)(?P=pair)$"));
+        assert!(!match_pattern("abba", "^(?P<pair>ab)(?P=pair)$"));
+        assert!(match_pattern("xyXY", "^(?i)(?<w>xy)\\k<w>$"));
+
+        assert_eq!(
+            Regex::new("(?<a>x)(?<a>y)").unwrap_err(),
+            PatternError::DuplicateGroupName { offset: 10, name: "a".to_string() }
+        );
+        assert_eq!(
+            Regex::new("a\\k<b>").unwrap_err(),
+            PatternError::UnknownGroupName { offset: 1, name: "b".to_string() }
+        );
+        assert_eq!(
+            Regex::new("(?<1a>x)").unwrap_err(),
+            PatternError::InvalidGroupName { offset: 3, name: "1a".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_multi_digit_and_relative_backreferences() {
+        let eleven = "(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)";
+        assert!(match_pattern("abcdefghijkk", &format!("^{}\\11$", eleven)));
+        assert!(!match_pattern("abcdefghijka1", &format!("^{}\\11$", eleven)));
+        assert!(match_pattern("abcdefghijka0", &format!("^{}\\g{{1}}0$", eleven)));
+        assert_eq!(
+            Regex::new("(a)(b)\\10").unwrap_err(),
+            PatternError::InvalidBackReference { offset: 6, group: 10 }
+        );
+
+        assert!(match_pattern("abb", "^(a)(b)\\g{-1}$"));
+        assert!(match_pattern("aba", "^(a)(b)\\g{-2}$"));
+        assert!(match_pattern("abb", "^(a)(b)\\g2$"));
+        assert!(match_pattern("xx", "^(?<x>x)\\g{x}$"));
+        assert_eq!(
+            Regex::new("(a)\\g{-2}").unwrap_err(),
+            PatternError::InvalidRelativeBackReference { offset: 3, distance: 2 }
+        );
+        assert_eq!(Regex::new("\\1(a)").unwrap_err(), PatternError::InvalidBackReference { offset: 0, group: 1 });
+        assert_eq!(Regex::new("(a)\\g{1").unwrap_err(), PatternError::InvalidBackReferenceSyntax { offset: 3 });
+        assert_eq!(Regex::new("(a)\\g{-}").unwrap_err(), PatternError::InvalidBackReferenceSyntax { offset: 3 });
+        assert_eq!(Regex::new("(a)\\gx").unwrap_err(), PatternError::InvalidBackReferenceSyntax { offset: 3 });
+        assert_eq!(Regex::new("(?<x>a)\\g{x").unwrap_err(), PatternError::InvalidBackReferenceSyntax { offset: 7 });
+    }
+
+    #[test]
+    fn test_lookaround() {
+        let number = Regex::new("\\d+(?![%\\d])").unwrap();
+        assert_eq!(number.find("50% of 120").unwrap().as_str(), "120");
+        assert!(!match_pattern("50%", "^\\d+(?![%\\d])"));
+        assert!(match_pattern("50%", "\\d+(?=%)"));
+        assert_eq!(Regex::new("foo(?=bar)").unwrap().find("foobaz foobar").unwrap().start(), 7);
+
+        let dollars = Regex::new("(?<=\\$)\\d+").unwrap();
+        assert_eq!(dollars.find("€5 $42").unwrap().as_str(), "42");
+        assert_eq!(Regex::new("(?<![$\\d])\\d+").unwrap().find("$42 17").unwrap().as_str(), "17");
+        assert!(match_pattern("xab", "(?<=x|yy)ab"));
+        assert!(match_pattern("yyab", "(?<=x|yy)ab"));
+        assert!(!match_pattern("zab", "(?<=x|yy)ab"));
+        assert!(match_pattern("é!", "(?<=\\p{L})!"));
+
+        let captures = Regex::new("(?=(\\w+))\\w").unwrap().captures("abc").unwrap();
+        assert_eq!(captures.get(1).unwrap().as_str(), "abc");
+        assert_eq!(captures.get(0).unwrap().as_str(), "a");
+
+        assert_eq!(Regex::new("a(?<=b+)").unwrap_err(), PatternError::UnboundedLookBehind { offset: 1 });
+        assert_eq!(Regex::new("(a)(?<=\\1)").unwrap_err(), PatternError::UnboundedLookBehind { offset: 3 });
+        let huge = format!("a(?<={}a{})", "(?:".repeat(6), "{1000})".repeat(7));
+        assert_eq!(Regex::new(&huge).unwrap_err(), PatternError::UnboundedLookBehind { offset: 1 });
+        assert!(Regex::new("(?<=a{1,3}|bc)d").is_ok());
+    }
+
+    #[test]
+    fn test_anchors_and_boundaries() {
+        assert!(match_pattern("foo bar", "(^foo|bar$)"));
+        assert!(match_pattern("xbar", "(^foo|bar$)"));
+        assert!(!match_pattern("xfoo barx", "(^foo|bar$)"));
+        assert!(match_pattern("ab", "a(b$|c)"));
+        assert!(!match_pattern("abx", "a(b$|c)"));
+        assert!(!match_pattern("For $5", "$\\d"));
+
+        let word = Regex::new("\\bcat\\b").unwrap();
+        assert_eq!(word.find("concat cat").unwrap().start(), 7);
+        assert!(!word.is_match("concatenate"));
+        assert!(match_pattern("concat", "\\Bcat"));
+        assert!(!match_pattern("cat", "\\Bcat"));
+        assert!(match_pattern("a cat", "\\<cat\\>"));
+        assert!(!match_pattern("cats", "\\<cat\\>"));
+        assert_eq!(Regex::new("\\>").unwrap().find("ab cd").unwrap().start(), 2);
+        assert!(match_pattern("naïve", "^na\\w+\\b$"));
+        assert!(match_pattern("naïve", "^(?-u)na\\b"));
+        assert!(match_pattern("日本語", "\\b日"));
+
+        assert!(match_pattern("ab\nc", "\\Aab"));
+        assert!(!match_pattern("x\nab", "(?m)\\Aab"));
+        assert!(match_pattern("x\nab", "(?m)^ab"));
+        assert!(match_pattern("ab\n", "ab\\Z"));
+        assert!(!match_pattern("ab\n", "ab\\z"));
+        assert!(match_pattern("ab", "ab\\z"));
+        let backtracked = Regex::new("\\b(a)\\1\\b").unwrap();
+        assert_eq!(backtracked.find("aaa aa").unwrap().start(), 4);
+    }
+
+    #[test]
+    fn test_top_level_alternation() {
+        assert!(match_pattern("hotdog", "cat|dog"));
+        assert!(match_pattern("cat", "cat|dog"));
+        assert!(!match_pattern("cow", "cat|dog"));
+        assert!(match_pattern("ax", "^a|b$"));
+        assert!(match_pattern("xb", "^a|b$"));
+        assert!(!match_pattern("xa bx", "^a|b$"));
+        assert!(match_pattern("", "a|"));
+        assert_eq!(Regex::new("x|(y)|z").unwrap().captures("z").unwrap().get(1), None);
+        assert_eq!(Regex::new("a|b)").unwrap_err(), PatternError::UnmatchedParenthesis { offset: 3 });
+        let documented = Regex::new("(?x) cat   # pets
+                                         | dog").unwrap();
+        assert!(documented.is_match("dog"));
+        assert!(match_pattern("a|b", "a\\|b"));
+        assert!(match_pattern("|", "[|]"));
+    }
+
+    #[test]
+    fn test_multi_line_and_dot_all() {
+        let buffer = "first line\nsecond line\nthird";
+        let starts: Vec<_> = Regex::new("(?m)^\\w+").unwrap().find_iter(buffer).map(|m| m.as_str()).collect();
+        assert_eq!(starts, vec!["first", "second", "third"]);
+        let ends: Vec<_> = Regex::new("(?m)\\w+$").unwrap().find_iter(buffer).map(|m| m.start()).collect();
+        assert_eq!(ends, vec![6, 18, 23]);
+        assert_eq!(Regex::new("^\\w+").unwrap().find_iter(buffer).count(), 1);
+
+        let multi_line = RegexBuilder::new("^second").multi_line(true).build().unwrap();
+        assert!(multi_line.is_match(buffer));
+        assert!(!Regex::new("^second").unwrap().is_match(buffer));
+        let scoped = RegexBuilder::new("(?-m)^second").multi_line(true).build().unwrap();
+        assert!(!scoped.is_match(buffer));
+
+        assert_eq!(Regex::new("line.second").unwrap().find(buffer), None);
+        let dot_all = RegexBuilder::new("line.second").dot_all(true).build().unwrap();
+        assert_eq!(dot_all.find(buffer).unwrap().range(), 6..17);
+        assert!(match_pattern(buffer, "(?s)first.*third"));
+        assert!(!match_pattern(buffer, "first.*third"));
+    }
+
+    #[test]
+    fn test_escape_sequences() {
+        assert!(match_pattern("a\tb", "^a\\tb$"));
+        assert!(match_pattern("a\nb", "a\\nb"));
+        assert!(match_pattern("A", "^\\x41$"));
+        assert!(match_pattern("A1", "^\\x411$"));
+        assert!(match_pattern("é", "^\\x{e9}$"));
+        assert!(match_pattern("é", "^\\u00E9$"));
+        assert!(match_pattern("😀", "^\\u{1F600}$"));
+        assert!(match_pattern("\0", "^\\0$"));
+        assert!(match_pattern("\u{7}", "^\\07$"));
+        assert!(match_pattern("\u{1}", "^\\cA$"));
+        assert!(match_pattern("\u{1b}[0m", "^\\e\\[0m$"));
+        assert!(match_pattern("a\tb", "^a[\\t ]b$"));
+        assert!(match_pattern("\u{1f}", "^[\\x00-\\x1F]$"));
+        assert!(!match_pattern(" ", "^[\\x00-\\x1F]$"));
+        assert!(match_pattern("É", "(?i)^\\u00e9$"));
+
+        assert!(match_pattern("a.b*c", "^\\Qa.b*c\\E$"));
+        assert!(!match_pattern("axbbc", "^\\Qa.b*c\\E$"));
+        assert!(match_pattern("1+11+1", "^(\\Q1+1\\E)+$"));
+        assert!(match_pattern("(x)", "\\Q(x)"));
+        assert!(match_pattern("*", "^[\\Q*]\\E]$"));
+
+        assert_eq!(Regex::new("a\\xZ").unwrap_err(), PatternError::InvalidEscape { offset: 1 });
+        assert_eq!(Regex::new("\\u{110000}").unwrap_err(), PatternError::InvalidEscape { offset: 0 });
+        assert_eq!(Regex::new("\\x{41").unwrap_err(), PatternError::InvalidEscape { offset: 0 });
+        assert_eq!(Regex::new("\\c").unwrap_err(), PatternError::InvalidEscape { offset: 0 });
+        assert_eq!(Regex::new("a\\y").unwrap_err(), PatternError::InvalidEscape { offset: 1 });
+        assert_eq!(Regex::new("ab\\E").unwrap_err(), PatternError::InvalidEscape { offset: 2 });
+        assert_eq!(Regex::new("\\kx").unwrap_err(), PatternError::InvalidEscape { offset: 0 });
+        assert_eq!(Regex::new("[\\B]").unwrap_err(), PatternError::InvalidEscape { offset: 1 });
+        assert!(match_pattern("a.b", "^a\\.b$"));
+        assert!(match_pattern("\u{8}", "^[\\b]$"));
+        assert!(!match_pattern("b", "[\\b]"));
+    }
+
+    #[test]
+    fn test_any_line_matches() {
+        let regex = Regex::new("ok\\r$").unwrap();
+        assert!(any_line_matches(&regex, &b"caf\xe9\nno\r\nwin ok\r\n"[..], b'\n').unwrap());
+        assert!(!any_line_matches(&regex, &b"ok\nwin ok\n"[..], b'\n').unwrap());
+        let regex = Regex::new("^caf.$").unwrap();
+        assert!(any_line_matches(&regex, &b"x\ncaf\xe9"[..], b'\n').unwrap());
+        assert!(any_line_matches(&Regex::new("a\nb").unwrap(), &b"a\nb\0c"[..], b'\0').unwrap());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use crate::error::PatternError;
+use crate::patterns::{Boundary, Pattern};
+
+#[derive(Clone, Debug)]
+pub(crate) enum Inst {
+    /// Matches one character against the class at this index of `Program::classes`.
+    Char(usize),
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    /// Ends an iteration whose start was saved in the slot: one that matched nothing goes
+    /// to the target, past the rest of the repetition, and any other goes on.
+    EmptyCheck(usize, usize),
+    Start,
+    End,
+    LineStart,
+    LineEnd,
+    EndBeforeNewline,
+    Boundary(Boundary, bool),
+    Match,
+}
+
+/// A Thompson NFA compiled from a pattern tree. `Split` prefers its first target, which
+/// keeps the leftmost-first priority of the backtracker. Group `n` saves its bounds in
+/// slots `2n` and `2n + 1`, group 0 being the whole match; the slots after those hold
+/// where the iterations of nested repetitions began.
+#[derive(Clone, Debug)]
+pub(crate) struct Program {
+    pub(crate) insts: Vec<Inst>,
+    /// The character patterns of the program, each stored once however many times a
+    /// counted repetition copies the instruction matching it.
+    pub(crate) classes: Vec<Pattern>,
+    pub(crate) slot_count: usize,
+    pub(crate) group_count: usize,
+    /// Whether any assertion looks at the characters around a position, which the lazy
+    /// DFA cannot track.
+    pub(crate) has_context_assertions: bool,
+}
+
+/// The most instructions a program may have. Counted repetitions copy their body, so
+/// nesting them multiplies the size; patterns over the limit are rejected.
+const PROGRAM_SIZE_LIMIT: usize = 100_000;
+
+fn prioritized_split(preferred: usize, other: usize, greedy: bool) -> Inst {
+    if greedy {
+        Inst::Split(preferred, other)
+    } else {
+        Inst::Split(other, preferred)
+    }
+}
+
+struct Compiler {
+    insts: Vec<Inst>,
+    classes: Vec<Pattern>,
+    /// The index in `classes` of each character pattern node already compiled.
+    class_indices: HashMap<*const Pattern, usize>,
+    group_slot_count: usize,
+    /// How many repetitions that can match nothing enclose the pattern being compiled.
+    empty_depth: usize,
+    slot_count: usize,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> usize {
+        self.insts.push(inst);
+        self.insts.len() - 1
+    }
+
+    fn next_index(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// Compiles one iteration of `p`. With `empty_slot`, an iteration that matches nothing
+    /// ends the repetition, as it does in the backtracker; `checks` collects the checks to
+    /// point at the end once it is known.
+    fn compile_iteration(&mut self, p: &Pattern, empty_slot: Option<usize>, checks: &mut Vec<usize>) -> Option<()> {
+        let Some(slot) = empty_slot else {
+            return self.compile(p);
+        };
+        self.push(Inst::Save(slot));
+        self.compile(p)?;
+        checks.push(self.push(Inst::EmptyCheck(slot, 0)));
+        Some(())
+    }
+
+    fn compile_repeat(&mut self, p: &Pattern, min: usize, max: Option<usize>, greedy: bool) -> Option<()> {
+        // Repetitions nested in one another need their own slots, but siblings can share.
+        let empty_slot = (p.length_bounds().0 == 0).then(|| {
+            self.empty_depth += 1;
+            self.slot_count = self.slot_count.max(self.group_slot_count + self.empty_depth);
+            self.group_slot_count + self.empty_depth - 1
+        });
+        let mut checks = vec![];
+        match max {
+            None => {
+                let skip = (min == 0).then(|| self.push(Inst::Split(0, 0)));
+                for _ in 1..min {
+                    self.compile(p)?;
+                }
+                let start = self.next_index();
+                self.compile_iteration(p, empty_slot, &mut checks)?;
+                let split = self.push(Inst::Split(start, 0));
+                // An empty iteration runs through instructions that the iteration before
+                // it may have visited at the same position, which the Pike VM does not
+                // revisit, so iterations alternate between two copies of the body.
+                let mut second = None;
+                if empty_slot.is_some() {
+                    let copy = self.next_index();
+                    self.compile_iteration(p, empty_slot, &mut checks)?;
+                    second = Some((copy, self.push(Inst::Split(start, 0))));
+                }
+                let end = self.next_index();
+                self.insts[split] = prioritized_split(second.map_or(start, |(copy, _)| copy), end, greedy);
+                if let Some((_, second_split)) = second {
+                    self.insts[second_split] = prioritized_split(start, end, greedy);
+                }
+                if let Some(skip) = skip {
+                    self.insts[skip] = prioritized_split(start, end, greedy);
+                }
+            },
+            Some(max) => {
+                let mut splits = vec![];
+                for count in 1..=max {
+                    if count > min {
+                        splits.push(self.push(Inst::Split(0, 0)));
+                    }
+                    if count >= min {
+                        self.compile_iteration(p, empty_slot, &mut checks)?;
+                    } else {
+                        self.compile(p)?;
+                    }
+                }
+                let end = self.next_index();
+                for split in splits {
+                    self.insts[split] = prioritized_split(split + 1, end, greedy);
+                }
+            },
+        }
+        if let Some(slot) = empty_slot {
+            let end = self.next_index();
+            for check in checks {
+                self.insts[check] = Inst::EmptyCheck(slot, end);
+            }
+            self.empty_depth -= 1;
+        }
+        Some(())
+    }
+
+    /// Returns `None` once the program has grown past [`PROGRAM_SIZE_LIMIT`].
+    fn compile(&mut self, pattern: &Pattern) -> Option<()> {
+        if self.insts.len() > PROGRAM_SIZE_LIMIT {
+            return None;
+        }
+        match pattern {
+            Pattern::Empty => {},
+            Pattern::SingleCharacter(_) | Pattern::Any(_, _) | Pattern::Wildcard => {
+                let next = self.classes.len();
+                let index = *self.class_indices.entry(pattern as *const Pattern).or_insert(next);
+                if index == next {
+                    self.classes.push(pattern.clone());
+                }
+                self.push(Inst::Char(index));
+            },
+            Pattern::Sequence(patterns) => {
+                for p in patterns {
+                    self.compile(p)?;
+                }
+            },
+            Pattern::Choice(alternatives) => {
+                let mut jumps = vec![];
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i + 1 == alternatives.len() {
+                        self.compile(alternative)?;
+                    } else {
+                        let split = self.push(Inst::Split(0, 0));
+                        self.compile(alternative)?;
+                        jumps.push(self.push(Inst::Jump(0)));
+                        self.insts[split] = Inst::Split(split + 1, self.next_index());
+                    }
+                }
+                let end = self.next_index();
+                for jump in jumps {
+                    self.insts[jump] = Inst::Jump(end);
+                }
+            },
+            Pattern::Group(p, index) => {
+                self.push(Inst::Save(index * 2));
+                self.compile(p)?;
+                self.push(Inst::Save(index * 2 + 1));
+            },
+            Pattern::Repeat(p, min, max, greedy) => self.compile_repeat(p, *min, *max, *greedy)?,
+            Pattern::Start => {
+                self.push(Inst::Start);
+            },
+            Pattern::End => {
+                self.push(Inst::End);
+            },
+            Pattern::LineStart => {
+                self.push(Inst::LineStart);
+            },
+            Pattern::LineEnd => {
+                self.push(Inst::LineEnd);
+            },
+            Pattern::EndBeforeNewline => {
+                self.push(Inst::EndBeforeNewline);
+            },
+            Pattern::Boundary(boundary, unicode) => {
+                self.push(Inst::Boundary(*boundary, *unicode));
+            },
+            Pattern::Atomic(_)
+            | Pattern::BackReference(_, _)
+            | Pattern::LookAhead(_, _)
+            | Pattern::LookBehind(_, _, _, _) => unreachable!("left to the backtracker by `compile`"),
+        }
+        Some(())
+    }
+}
+
+/// Whether the pattern uses a feature that an automaton cannot express.
+fn needs_backtracking(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Atomic(_) | Pattern::BackReference(_, _) | Pattern::LookAhead(_, _) | Pattern::LookBehind(_, _, _, _) => {
+            true
+        },
+        Pattern::Sequence(patterns) | Pattern::Choice(patterns) => patterns.iter().any(needs_backtracking),
+        Pattern::Group(p, _) | Pattern::Repeat(p, _, _, _) => needs_backtracking(p),
+        _ => false,
+    }
+}
+
+/// Returns `None` when the pattern needs features an automaton cannot express, and an
+/// error when its program would exceed [`PROGRAM_SIZE_LIMIT`] instructions.
+pub(crate) fn compile(pattern: &Pattern, group_count: usize) -> Result<Option<Program>, PatternError> {
+    if needs_backtracking(pattern) {
+        return Ok(None);
+    }
+    let group_slot_count = (group_count + 1) * 2;
+    let mut compiler = Compiler {
+        insts: vec![],
+        classes: vec![],
+        class_indices: HashMap::new(),
+        group_slot_count,
+        empty_depth: 0,
+        slot_count: group_slot_count,
+    };
+    compiler.push(Inst::Save(0));
+    if compiler.compile(pattern).is_none() {
+        return Err(PatternError::PatternTooLarge { offset: 0, limit: PROGRAM_SIZE_LIMIT });
+    }
+    compiler.push(Inst::Save(1));
+    compiler.push(Inst::Match);
+    let has_context_assertions = compiler
+        .insts
+        .iter()
+        .any(|inst| matches!(inst, Inst::LineStart | Inst::LineEnd | Inst::EndBeforeNewline | Inst::Boundary(_, _)));
+    Ok(Some(Program {
+        insts: compiler.insts,
+        classes: compiler.classes,
+        slot_count: compiler.slot_count,
+        group_count,
+        has_context_assertions,
+    }))
+}
@@ -0,0 +1,687 @@
+use crate::classes::{self, ClassSet, Shorthand};
+use crate::error::PatternError;
+use crate::patterns::{Boundary, Pattern};
+use crate::unicode::{self, CaseFolding};
+
+const REPETITION_LIMIT: usize = 1000;
+
+/// Options that change how the pattern is read. Inline groups such as `(?-u)` override
+/// them until the end of the enclosing group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flags {
+    /// Shorthand classes and case folding cover all of Unicode rather than only ASCII.
+    pub unicode: bool,
+    pub case_insensitive: bool,
+    /// `^` and `$` also match next to a line feed.
+    pub multi_line: bool,
+    /// `.` also matches a line feed.
+    pub dot_all: bool,
+    /// Whitespace and `#` comments outside brackets are ignored.
+    pub extended: bool,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Flags { unicode: true, case_insensitive: false, multi_line: false, dot_all: false, extended: false }
+    }
+}
+
+impl Flags {
+    fn case_folding(self) -> CaseFolding {
+        match (self.case_insensitive, self.unicode) {
+            (false, _) => CaseFolding::Exact,
+            (true, false) => CaseFolding::Ascii,
+            (true, true) => CaseFolding::Unicode,
+        }
+    }
+}
+
+/// The result of parsing: the pattern tree, how many capture groups it has and the
+/// numbers of its named groups.
+#[derive(Clone, Debug)]
+pub struct ParsedPattern {
+    pub pattern: Pattern,
+    pub group_count: usize,
+    pub group_names: Vec<(String, usize)>,
+    /// Whether any character the pattern matches literally, written plainly or as an
+    /// escape, is uppercase, which turns off smart case.
+    pub has_uppercase_literal: bool,
+}
+
+enum GroupKind {
+    Capture(usize),
+    NonCapturing,
+    Atomic,
+    LookAhead(bool),
+    LookBehind(bool),
+}
+
+enum BracketMember {
+    Char(char),
+    Set(ClassSet),
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    position: usize,
+    depth: usize,
+    group_count: usize,
+    group_names: Vec<(String, usize)>,
+    flags: Flags,
+    has_uppercase_literal: bool,
+}
+
+impl Parser {
+    fn new(pattern: &str, flags: Flags) -> Self {
+        Parser {
+            chars: pattern.char_indices().collect(),
+            position: 0,
+            depth: 0,
+            group_count: 0,
+            group_names: vec![],
+            flags,
+            has_uppercase_literal: false,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).map(|(_, c)| *c)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn skip_extended(&mut self) {
+        if !self.flags.extended {
+            return;
+        }
+        while let Some(c) = self.peek() {
+            match c {
+                '#' => {
+                    while self.next().is_some_and(|c| c != '\n') {}
+                },
+                c if c.is_whitespace() => {
+                    self.next();
+                },
+                _ => break,
+            }
+        }
+    }
+
+    fn is_at_sequence_end(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(c) => c == '|' || (c == ')' && self.depth > 0),
+        }
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        self.chars[index].0
+    }
+
+    fn parse_choice(&mut self) -> Result<Pattern, PatternError> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.next();
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(Pattern::choice(alternatives))
+    }
+
+    fn parse_sequence(&mut self) -> Result<Pattern, PatternError> {
+        let mut patterns: Vec<Pattern> = vec![];
+        loop {
+            self.skip_extended();
+            if self.is_at_sequence_end() {
+                break;
+            }
+            if self.peek() == Some(')') {
+                return Err(PatternError::UnmatchedParenthesis { offset: self.offset(self.position) });
+            }
+            patterns.push(self.parse_repetition()?);
+        }
+        Ok(Pattern::sequence(patterns))
+    }
+
+    fn parse_repetition(&mut self) -> Result<Pattern, PatternError> {
+        let atom = self.parse_atom()?;
+        self.skip_extended();
+        let (min, max) = match self.peek() {
+            Some('{') => match self.parse_counter()? {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.next();
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            },
+            _ => return Ok(atom),
+        };
+        let repeat = match self.peek() {
+            Some('?') => {
+                self.next();
+                Pattern::lazy_repeat(atom, min, max)
+            },
+            Some('+') => {
+                self.next();
+                Pattern::atomic(Pattern::repeat(atom, min, max))
+            },
+            _ => Pattern::repeat(atom, min, max),
+        };
+        // A quantifier cannot apply to a quantifier; `(?:a*)*` spells out what `a**` means.
+        self.skip_extended();
+        let index = self.position;
+        let is_quantifier = match self.peek() {
+            Some('*' | '+' | '?') => true,
+            Some('{') => self.parse_counter()?.is_some(),
+            _ => false,
+        };
+        if is_quantifier {
+            return Err(PatternError::RepeatedQuantifier { offset: self.offset(index) });
+        }
+        Ok(repeat)
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.next();
+        }
+        let digits: String = self.chars[start..self.position].iter().map(|(_, c)| c).collect();
+        if digits.is_empty() {
+            None
+        } else {
+            Some(digits.parse().unwrap_or(usize::MAX))
+        }
+    }
+
+    fn parse_counter(&mut self) -> Result<Option<(usize, Option<usize>)>, PatternError> {
+        let index = self.position;
+        self.next();
+        let min = self.parse_number();
+        let max = if self.peek() == Some(',') {
+            self.next();
+            self.parse_number()
+        } else {
+            min
+        };
+        if self.next() != Some('}') || (min.is_none() && max.is_none()) {
+            self.position = index;
+            return Ok(None);
+        }
+        let min = min.unwrap_or(0);
+        let offset = self.offset(index);
+        if max.is_some_and(|max| max < min) {
+            return Err(PatternError::InvalidRepetitionRange { offset });
+        }
+        if min.max(max.unwrap_or(0)) > REPETITION_LIMIT {
+            return Err(PatternError::RepetitionTooLarge { offset, limit: REPETITION_LIMIT });
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_atom(&mut self) -> Result<Pattern, PatternError> {
+        let index = self.position;
+        let char = self.next().unwrap();
+        match char {
+            '(' => self.parse_group(index),
+            '[' => self.parse_bracket(index),
+            '\\' => self.parse_escape(index),
+            '.' if self.flags.dot_all => Ok(Pattern::wildcard()),
+            '.' => Ok(Pattern::any(ClassSet::from_ranges(&[('\n', '\n')]), true)),
+            '^' if self.flags.multi_line => Ok(Pattern::LineStart),
+            '^' => Ok(Pattern::Start),
+            '$' if self.flags.multi_line => Ok(Pattern::LineEnd),
+            '$' => Ok(Pattern::End),
+            _ => Ok(self.literal(char)),
+        }
+    }
+
+    fn note_literal(&mut self, c: char) {
+        self.has_uppercase_literal |= c.is_uppercase();
+    }
+
+    fn literal(&mut self, c: char) -> Pattern {
+        self.note_literal(c);
+        let mut set = ClassSet::from_ranges(&[(c, c)]);
+        set.case_fold(self.flags.case_folding());
+        if set.ranges() == [(c, c)] {
+            Pattern::single_character(c)
+        } else {
+            Pattern::any(set, false)
+        }
+    }
+
+    /// Parses `(...)`, `(?<name>...)`, `(?>...)`, the lookarounds `(?=...)`, `(?!...)`,
+    /// `(?<=...)` and `(?<!...)`, `(?flags)` and `(?flags:...)`, of which `(?:...)` is the
+    /// case with no flags, as well as the `(?P=name)` back reference. Only plain and named
+    /// parentheses capture, and they are numbered by their opening parenthesis, left to
+    /// right.
+    fn parse_group(&mut self, index: usize) -> Result<Pattern, PatternError> {
+        let outer_flags = self.flags;
+        let kind = if self.peek() == Some('?') {
+            self.next();
+            let invalid = PatternError::InvalidGroup { offset: self.offset(index) };
+            match (self.peek(), self.chars.get(self.position + 1).map(|(_, c)| *c)) {
+                (Some(c @ ('>' | '=' | '!')), _) => {
+                    self.next();
+                    match c {
+                        '>' => GroupKind::Atomic,
+                        _ => GroupKind::LookAhead(c == '!'),
+                    }
+                },
+                (Some('<'), Some(c @ ('=' | '!'))) => {
+                    self.position += 2;
+                    GroupKind::LookBehind(c == '!')
+                },
+                (Some('P'), Some('=')) => {
+                    self.position += 2;
+                    let name = self.parse_group_name(')', invalid)?;
+                    return self.named_backreference(index, name);
+                },
+                (Some('P'), Some('<')) | (Some('<'), _) => {
+                    self.position += if self.peek() == Some('P') { 2 } else { 1 };
+                    let name_index = self.position;
+                    let name = self.parse_group_name('>', invalid)?;
+                    if self.group_names.iter().any(|(n, _)| *n == name) {
+                        return Err(PatternError::DuplicateGroupName { offset: self.offset(name_index), name });
+                    }
+                    self.group_count += 1;
+                    self.group_names.push((name, self.group_count));
+                    GroupKind::Capture(self.group_count)
+                },
+                _ => {
+                    self.flags = self.parse_flags(index)?;
+                    if self.next() == Some(')') {
+                        return Ok(Pattern::Empty);
+                    }
+                    GroupKind::NonCapturing
+                },
+            }
+        } else {
+            self.group_count += 1;
+            GroupKind::Capture(self.group_count)
+        };
+        self.depth += 1;
+        let inner = self.parse_choice()?;
+        self.depth -= 1;
+        self.flags = outer_flags;
+        if self.next() != Some(')') {
+            return Err(PatternError::UnclosedGroup { offset: self.offset(index) });
+        }
+        Ok(match kind {
+            GroupKind::Capture(group_index) => Pattern::group(inner, group_index),
+            GroupKind::NonCapturing => inner,
+            GroupKind::Atomic => Pattern::atomic(inner),
+            GroupKind::LookAhead(is_negative) => Pattern::look_ahead(inner, is_negative),
+            GroupKind::LookBehind(is_negative) => match inner.length_bounds() {
+                (min, Some(max)) => Pattern::look_behind(inner, is_negative, min, max),
+                (_, None) => return Err(PatternError::UnboundedLookBehind { offset: self.offset(index) }),
+            },
+        })
+    }
+
+    /// Reads a group name up to and including `terminator`. A name is a letter or `_`
+    /// followed by letters, digits and `_`.
+    fn parse_group_name(&mut self, terminator: char, unterminated: PatternError) -> Result<String, PatternError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != terminator) {
+            self.next();
+        }
+        if self.next().is_none() {
+            return Err(unterminated);
+        }
+        let name: String = self.chars[start..self.position - 1].iter().map(|(_, c)| c).collect();
+        let is_valid = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !is_valid {
+            let offset = self.chars.get(start).map_or(self.offset(start - 1), |(offset, _)| *offset);
+            return Err(PatternError::InvalidGroupName { offset, name });
+        }
+        Ok(name)
+    }
+
+    fn numbered_backreference(&self, index: usize, group: usize) -> Result<Pattern, PatternError> {
+        if group == 0 || group > self.group_count {
+            return Err(PatternError::InvalidBackReference { offset: self.offset(index), group });
+        }
+        Ok(Pattern::backreference(group, self.flags.case_folding()))
+    }
+
+    /// Parses the part after `\g`: `\gN`, `\g{N}`, `\g{-N}` counting back from the
+    /// latest opened group, or `\g{name}`.
+    fn parse_g_reference(&mut self, index: usize) -> Result<Pattern, PatternError> {
+        let invalid = PatternError::InvalidBackReferenceSyntax { offset: self.offset(index) };
+        if self.peek() != Some('{') {
+            let group = self.parse_number().ok_or(invalid)?;
+            return self.numbered_backreference(index, group);
+        }
+        self.next();
+        let name = match self.peek() {
+            Some('-') => {
+                self.next();
+                let distance = self.parse_number().filter(|_| self.next() == Some('}')).ok_or(invalid)?;
+                if distance == 0 || distance > self.group_count {
+                    return Err(PatternError::InvalidRelativeBackReference { offset: self.offset(index), distance });
+                }
+                return self.numbered_backreference(index, self.group_count + 1 - distance);
+            },
+            Some(c) if c.is_ascii_digit() => {
+                let group = self.parse_number().filter(|_| self.next() == Some('}')).ok_or(invalid)?;
+                return self.numbered_backreference(index, group);
+            },
+            _ => self.parse_group_name('}', invalid)?,
+        };
+        self.named_backreference(index, name)
+    }
+
+    fn named_backreference(&self, index: usize, name: String) -> Result<Pattern, PatternError> {
+        match self.group_names.iter().find(|(n, _)| *n == name) {
+            Some((_, group)) => Ok(Pattern::backreference(*group, self.flags.case_folding())),
+            None => Err(PatternError::UnknownGroupName { offset: self.offset(index), name }),
+        }
+    }
+
+    /// Reads the letters of `(?flags)` or `(?flags:...)` up to, but not including, the
+    /// closing `)` or `:`.
+    fn parse_flags(&mut self, index: usize) -> Result<Flags, PatternError> {
+        let mut flags = self.flags;
+        let mut is_negative = false;
+        loop {
+            let flag_index = self.position;
+            match self.peek() {
+                Some(')' | ':') => return Ok(flags),
+                Some('-') if !is_negative => is_negative = true,
+                Some('i') => flags.case_insensitive = !is_negative,
+                Some('m') => flags.multi_line = !is_negative,
+                Some('s') => flags.dot_all = !is_negative,
+                Some('u') => flags.unicode = !is_negative,
+                Some('x') => flags.extended = !is_negative,
+                Some(flag) if flag.is_ascii_alphabetic() => {
+                    return Err(PatternError::UnknownFlag { offset: self.offset(flag_index), flag });
+                },
+                _ => return Err(PatternError::InvalidGroup { offset: self.offset(index) }),
+            }
+            self.next();
+        }
+    }
+
+    fn shorthand_class(&self, class: Shorthand, is_negative: bool) -> ClassSet {
+        let mut set = class.class_set(self.flags.unicode);
+        set.case_fold(self.flags.case_folding());
+        if is_negative {
+            set.complement()
+        } else {
+            set
+        }
+    }
+
+    fn parse_escape(&mut self, index: usize) -> Result<Pattern, PatternError> {
+        let Some(c) = self.next() else {
+            return Err(PatternError::TrailingBackslash { offset: self.offset(index) });
+        };
+        if let Some((class, is_negative)) = classes::shorthand(c) {
+            return Ok(Pattern::any(self.shorthand_class(class, is_negative), false));
+        }
+        match c {
+            'p' | 'P' => self.parse_property(index, c == 'P'),
+            'b' => Ok(Pattern::Boundary(Boundary::Word, self.flags.unicode)),
+            'B' => Ok(Pattern::Boundary(Boundary::NotWord, self.flags.unicode)),
+            '<' => Ok(Pattern::Boundary(Boundary::WordStart, self.flags.unicode)),
+            '>' => Ok(Pattern::Boundary(Boundary::WordEnd, self.flags.unicode)),
+            'A' => Ok(Pattern::Start),
+            'z' => Ok(Pattern::End),
+            'Z' => Ok(Pattern::EndBeforeNewline),
+            'k' if self.peek() == Some('<') => {
+                self.next();
+                let unterminated = PatternError::InvalidGroupName { offset: self.offset(index), name: String::new() };
+                let name = self.parse_group_name('>', unterminated)?;
+                self.named_backreference(index, name)
+            },
+            '1'..='9' => {
+                self.position -= 1;
+                let group = self.parse_number().unwrap();
+                self.numbered_backreference(index, group)
+            },
+            'g' => self.parse_g_reference(index),
+            'Q' => {
+                let quoted = self.parse_quoted().into_iter().map(|c| self.literal(c)).collect();
+                Ok(Pattern::sequence(quoted))
+            },
+            _ => {
+                let c = self.parse_char_escape(index, c)?;
+                Ok(self.literal(c))
+            },
+        }
+    }
+
+    /// Resolves the character named by the escape `\c`, where `c` has just been read:
+    /// `\t`, `\n`, `\r`, `\f`, `\a`, `\e`, `\xHH`, `\x{H...}`, `\uHHHH`, `\u{H...}`,
+    /// `\0` with up to two octal digits, and the control character `\cX`. Any other
+    /// character that is not an ASCII letter or digit stands for itself.
+    fn parse_char_escape(&mut self, index: usize, c: char) -> Result<char, PatternError> {
+        let offset = self.offset(index);
+        let invalid = || PatternError::InvalidEscape { offset };
+        let code = match c {
+            't' => 0x09,
+            'n' => 0x0A,
+            'r' => 0x0D,
+            'f' => 0x0C,
+            'a' => 0x07,
+            'e' => 0x1B,
+            'x' => self.parse_code_point(2).ok_or_else(invalid)?,
+            'u' => self.parse_code_point(4).ok_or_else(invalid)?,
+            '0' => {
+                let mut code = 0;
+                for _ in 0..2 {
+                    match self.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => code = code * 8 + digit,
+                        None => break,
+                    }
+                    self.next();
+                }
+                code
+            },
+            'c' => match self.next() {
+                Some(control) if control.is_ascii() => control.to_ascii_uppercase() as u32 ^ 0x40,
+                _ => return Err(invalid()),
+            },
+            c if c.is_ascii_alphanumeric() => return Err(invalid()),
+            _ => return Ok(c),
+        };
+        char::from_u32(code).ok_or_else(invalid)
+    }
+
+    /// Reads the hex digits after `\x` or `\u`: either up to `digits` of them or any
+    /// number in braces.
+    fn parse_code_point(&mut self, digits: usize) -> Option<u32> {
+        let is_braced = self.peek() == Some('{');
+        if is_braced {
+            self.next();
+        }
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) && (is_braced || self.position - start < digits) {
+            self.next();
+        }
+        let hex: String = self.chars[start..self.position].iter().map(|(_, c)| c).collect();
+        if is_braced && self.next() != Some('}') {
+            return None;
+        }
+        u32::from_str_radix(&hex, 16).ok()
+    }
+
+    /// Reads the characters of `\Q...\E` after the `Q`, up to `\E` or the end of the
+    /// pattern. The quoted text is one atom, so a quantifier after `\E` repeats all of it.
+    fn parse_quoted(&mut self) -> Vec<char> {
+        let mut quoted = vec![];
+        while let Some(c) = self.next() {
+            if c == '\\' && self.peek() == Some('E') {
+                self.next();
+                break;
+            }
+            quoted.push(c);
+        }
+        quoted
+    }
+
+    fn parse_bracket_escape(&mut self, index: usize) -> Result<BracketMember, PatternError> {
+        let Some(c) = self.next() else {
+            return Err(PatternError::TrailingBackslash { offset: self.offset(index) });
+        };
+        if c == 'p' || c == 'P' {
+            return self.parse_property_class(index, c == 'P').map(BracketMember::Set);
+        }
+        if c == 'Q' {
+            let mut set = ClassSet::default();
+            for c in self.parse_quoted() {
+                self.note_literal(c);
+                set.add_char(c);
+            }
+            set.canonicalize();
+            return Ok(BracketMember::Set(set));
+        }
+        match classes::shorthand(c) {
+            Some((class, is_negative)) => Ok(BracketMember::Set(self.shorthand_class(class, is_negative))),
+            // A word boundary means nothing inside brackets, so `[\b]` is a backspace.
+            None if c == 'b' => Ok(BracketMember::Char('\u{8}')),
+            None => Ok(BracketMember::Char(self.parse_char_escape(index, c)?)),
+        }
+    }
+
+    fn parse_property(&mut self, index: usize, is_negative: bool) -> Result<Pattern, PatternError> {
+        Ok(Pattern::any(self.parse_property_class(index, is_negative)?, false))
+    }
+
+    fn parse_property_class(&mut self, index: usize, mut is_negative: bool) -> Result<ClassSet, PatternError> {
+        let offset = self.offset(index);
+        let name: String = match self.next() {
+            Some('{') => {
+                let start = self.position;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.next();
+                }
+                let name = self.chars[start..self.position].iter().map(|(_, c)| c).collect();
+                if self.next().is_none() {
+                    return Err(PatternError::UnknownProperty { offset, name });
+                }
+                name
+            },
+            Some(c) => c.to_string(),
+            None => return Err(PatternError::TrailingBackslash { offset }),
+        };
+        let lookup_name = match name.strip_prefix('^') {
+            Some(rest) => {
+                is_negative = !is_negative;
+                rest
+            },
+            None => &name,
+        };
+        let Some(mut set) = unicode::property_class(lookup_name) else {
+            return Err(PatternError::UnknownProperty { offset, name });
+        };
+        set.case_fold(self.flags.case_folding());
+        Ok(if is_negative { set.complement() } else { set })
+    }
+
+    fn parse_bracket_member(&mut self, bracket_index: usize) -> Result<BracketMember, PatternError> {
+        let index = self.position;
+        match self.next() {
+            None => Err(PatternError::UnclosedBracket { offset: self.offset(bracket_index) }),
+            Some('\\') => self.parse_bracket_escape(index),
+            Some('[') if matches!(self.peek(), Some(':' | '=' | '.')) => self.parse_bracket_item(index, bracket_index),
+            Some(c) => Ok(BracketMember::Char(c)),
+        }
+    }
+
+    fn parse_bracket_item(&mut self, index: usize, bracket_index: usize) -> Result<BracketMember, PatternError> {
+        let kind = self.next().unwrap();
+        let start = self.position;
+        loop {
+            match self.next() {
+                None => return Err(PatternError::UnclosedBracket { offset: self.offset(bracket_index) }),
+                Some(c) if c == kind && self.peek() == Some(']') => break,
+                Some(_) => {},
+            }
+        }
+        self.next();
+        let name: String = self.chars[start..self.position - 2].iter().map(|(_, c)| c).collect();
+        let offset = self.offset(index);
+        match kind {
+            ':' => match classes::posix_class(&name, self.flags.unicode) {
+                Some(set) => Ok(BracketMember::Set(set)),
+                None => Err(PatternError::UnknownClass { offset, name }),
+            },
+            '=' => match classes::collating_element(&name) {
+                Some(c) => Ok(BracketMember::Set(classes::equivalence_class(c))),
+                None => Err(PatternError::InvalidCollatingElement { offset, name }),
+            },
+            _ => match classes::collating_element(&name) {
+                Some(c) => Ok(BracketMember::Char(c)),
+                None => Err(PatternError::InvalidCollatingElement { offset, name }),
+            },
+        }
+    }
+
+    fn parse_bracket(&mut self, index: usize) -> Result<Pattern, PatternError> {
+        let mut set = ClassSet::default();
+        let mut is_negative = false;
+        if self.peek() == Some('^') {
+            self.next();
+            is_negative = true;
+        }
+        let mut is_first = true;
+        loop {
+            let member_index = self.position;
+            if self.peek() == Some(']') && !is_first {
+                self.next();
+                break;
+            }
+            is_first = false;
+            let start = match self.parse_bracket_member(index)? {
+                BracketMember::Char(start) => {
+                    self.note_literal(start);
+                    start
+                },
+                BracketMember::Set(members) => {
+                    set.union(&members);
+                    continue;
+                },
+            };
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.position + 1).is_some_and(|(_, c)| *c != ']');
+            if !is_range {
+                set.add_char(start);
+                continue;
+            }
+            self.next();
+            match self.parse_bracket_member(index)? {
+                BracketMember::Char(end) if start <= end => {
+                    self.note_literal(end);
+                    set.add_range(start, end)
+                },
+                _ => return Err(PatternError::InvalidRange { offset: self.offset(member_index) }),
+            }
+        }
+        set.canonicalize();
+        set.case_fold(self.flags.case_folding());
+        Ok(Pattern::any(set, is_negative))
+    }
+}
+
+pub fn parse(pattern: &str, flags: Flags) -> Result<ParsedPattern, PatternError> {
+    let mut parser = Parser::new(pattern, flags);
+    let pattern = parser.parse_choice()?;
+    Ok(ParsedPattern {
+        pattern,
+        group_count: parser.group_count,
+        group_names: parser.group_names,
+        has_uppercase_literal: parser.has_uppercase_literal,
+    })
+}
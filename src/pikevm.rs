@@ -0,0 +1,200 @@
+use std::mem;
+
+use crate::nfa::{Inst, Program};
+use crate::patterns::{self, Slots};
+
+/// The threads at one position, as a sparse set of instructions, with the slots of each
+/// thread stored in one flat buffer of `slot_count` entries per instruction.
+#[derive(Debug, Default)]
+struct Threads {
+    dense: Vec<usize>,
+    sparse: Vec<usize>,
+    slots: Vec<Option<usize>>,
+    slot_count: usize,
+}
+
+impl Threads {
+    fn reset(&mut self, program: &Program) {
+        self.dense.clear();
+        if self.sparse.len() != program.insts.len() || self.slot_count != program.slot_count {
+            self.sparse = vec![0; program.insts.len()];
+            self.slots = vec![None; program.insts.len() * program.slot_count];
+            self.slot_count = program.slot_count;
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        self.sparse[pc] < self.dense.len() && self.dense[self.sparse[pc]] == pc
+    }
+
+    fn insert(&mut self, pc: usize) {
+        self.sparse[pc] = self.dense.len();
+        self.dense.push(pc);
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+
+    fn slots(&self, pc: usize) -> &[Option<usize>] {
+        &self.slots[pc * self.slot_count..(pc + 1) * self.slot_count]
+    }
+
+    fn slots_mut(&mut self, pc: usize) -> &mut [Option<usize>] {
+        &mut self.slots[pc * self.slot_count..(pc + 1) * self.slot_count]
+    }
+}
+
+/// The buffers of a search, kept by a `Regex` so that searching line after line does
+/// not allocate them again each time.
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    current: Threads,
+    next: Threads,
+    stack: Vec<Frame>,
+    slots: Vec<Option<usize>>,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Explore(usize),
+    Restore(usize, Option<usize>),
+}
+
+struct PikeVM<'p, 'h, 'c> {
+    program: &'p Program,
+    haystack: &'h str,
+    stack: &'c mut Vec<Frame>,
+}
+
+impl PikeVM<'_, '_, '_> {
+    fn add_thread(&mut self, threads: &mut Threads, pc: usize, position: usize, slots: &mut [Option<usize>]) {
+        self.stack.push(Frame::Explore(pc));
+        while let Some(frame) = self.stack.pop() {
+            let pc = match frame {
+                Frame::Explore(pc) => pc,
+                Frame::Restore(index, value) => {
+                    slots[index] = value;
+                    continue;
+                },
+            };
+            if threads.contains(pc) {
+                continue;
+            }
+            threads.insert(pc);
+            match self.program.insts[pc] {
+                Inst::Jump(target) => self.stack.push(Frame::Explore(target)),
+                Inst::Split(first, second) => {
+                    self.stack.push(Frame::Explore(second));
+                    self.stack.push(Frame::Explore(first));
+                },
+                Inst::Save(index) => {
+                    self.stack.push(Frame::Restore(index, slots[index]));
+                    slots[index] = Some(position);
+                    self.stack.push(Frame::Explore(pc + 1));
+                },
+                Inst::EmptyCheck(index, exit) => {
+                    let target = if slots[index] == Some(position) { exit } else { pc + 1 };
+                    self.stack.push(Frame::Explore(target));
+                },
+                Inst::Start => {
+                    if position == 0 {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::End => {
+                    if position == self.haystack.len() {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::LineStart => {
+                    if position == 0 || self.haystack[..position].ends_with('\n') {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::LineEnd => {
+                    if position == self.haystack.len() || self.haystack[position..].starts_with('\n') {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::EndBeforeNewline => {
+                    if patterns::is_end_before_newline(self.haystack, position) {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::Boundary(boundary, unicode) => {
+                    if boundary.matches(unicode, self.haystack, position) {
+                        self.stack.push(Frame::Explore(pc + 1));
+                    }
+                },
+                Inst::Char(_) | Inst::Match => threads.slots_mut(pc).copy_from_slice(slots),
+            }
+        }
+    }
+
+    fn run(
+        &mut self,
+        from: usize,
+        current: &mut Threads,
+        next: &mut Threads,
+        slots: &mut Vec<Option<usize>>,
+    ) -> Option<Vec<Option<usize>>> {
+        current.reset(self.program);
+        next.reset(self.program);
+        slots.clear();
+        slots.resize(self.program.slot_count, None);
+        let mut matched = None;
+        let mut position = from;
+        loop {
+            if matched.is_none() {
+                slots.fill(None);
+                self.add_thread(current, 0, position, slots);
+            }
+            let c = self.haystack[position..].chars().next();
+            for i in 0..current.dense.len() {
+                let pc = current.dense[i];
+                match &self.program.insts[pc] {
+                    Inst::Char(class) => {
+                        if let Some(c) = c.filter(|c| self.program.classes[*class].matches_char(*c)) {
+                            slots.copy_from_slice(current.slots(pc));
+                            self.add_thread(next, pc + 1, position + c.len_utf8(), slots);
+                        }
+                    },
+                    Inst::Match => {
+                        matched = Some(current.slots(pc).to_vec());
+                        break;
+                    },
+                    _ => {},
+                }
+            }
+            let Some(c) = c else {
+                break;
+            };
+            if matched.is_some() && next.dense.is_empty() {
+                break;
+            }
+            position += c.len_utf8();
+            mem::swap(current, next);
+            next.clear();
+        }
+        matched
+    }
+}
+
+/// Simulates every NFA thread in lockstep, so the search runs in
+/// O(program size × haystack length) whatever the pattern.
+pub(crate) fn search(program: &Program, cache: &mut Cache, haystack: &str, from: usize) -> Option<Slots> {
+    let Cache { current, next, stack, slots } = cache;
+    let mut vm = PikeVM { program, haystack, stack };
+    let found = vm.run(from, current, next, slots)?;
+    Some(
+        found
+            .chunks(2)
+            .take(program.group_count + 1)
+            .map(|bounds| match bounds {
+                [Some(start), Some(end)] => Some((*start, *end)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,279 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::backtrack;
+use crate::dfa;
+use crate::error::{MatchError, PatternError};
+use crate::nfa::{self, Program};
+use crate::parser::{parse, Flags};
+use crate::patterns::{Pattern, Slots};
+use crate::pikevm;
+
+/// A compiled pattern. The engines step through the haystack one `char` at a time, and
+/// every position they report or accept is a byte offset on a UTF-8 boundary, so spans
+/// can be used to slice the haystack directly.
+#[derive(Debug)]
+pub struct Regex {
+    pattern: Pattern,
+    group_count: usize,
+    group_names: Arc<[(String, usize)]>,
+    program: Option<Program>,
+    dfa_cache: Mutex<dfa::Cache>,
+    pike_cache: Mutex<pikevm::Cache>,
+    backtrack_limit: usize,
+}
+
+impl Clone for Regex {
+    fn clone(&self) -> Self {
+        Regex {
+            pattern: self.pattern.clone(),
+            group_count: self.group_count,
+            group_names: self.group_names.clone(),
+            program: self.program.clone(),
+            dfa_cache: Mutex::default(),
+            pike_cache: Mutex::default(),
+            backtrack_limit: self.backtrack_limit,
+        }
+    }
+}
+
+/// A span of the haystack matched by the whole pattern or by one group, in byte offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.start..self.end]
+    }
+}
+
+/// The spans of every numbered group for one match; group 0 is the whole match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    slots: Slots,
+    group_names: Arc<[(String, usize)]>,
+}
+
+impl<'h> Captures<'h> {
+    pub fn get(&self, index: usize) -> Option<Match<'h>> {
+        let (start, end) = (*self.slots.get(index)?)?;
+        Some(Match { haystack: self.haystack, start, end })
+    }
+
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        let (_, index) = self.group_names.iter().find(|(n, _)| n == name)?;
+        self.get(*index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
+
+pub struct Matches<'r, 'h> {
+    regex: &'r Regex,
+    haystack: &'h str,
+    position: Option<usize>,
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        let position = self.position?;
+        let slots = self.regex.search_from(self.haystack, position).unwrap_or_else(|error| panic!("{error}"));
+        let Some((start, end)) = slots.and_then(|slots| slots[0]) else {
+            self.position = None;
+            return None;
+        };
+        self.position = if end > start {
+            Some(end)
+        } else {
+            self.haystack[end..].chars().next().map(|c| end + c.len_utf8())
+        };
+        Some(Match { haystack: self.haystack, start, end })
+    }
+}
+
+/// Compiles a pattern with options other than the defaults used by [`Regex::new`].
+#[derive(Clone, Debug)]
+pub struct RegexBuilder {
+    pattern: String,
+    flags: Flags,
+    smart_case: bool,
+    backtrack_limit: usize,
+}
+
+impl RegexBuilder {
+    pub fn new(pattern: &str) -> Self {
+        RegexBuilder {
+            pattern: pattern.to_string(),
+            flags: Flags::default(),
+            smart_case: false,
+            backtrack_limit: backtrack::DEFAULT_STEP_LIMIT,
+        }
+    }
+
+    /// Whether `\d`, `\w`, `\s` and the other shorthands cover Unicode or only ASCII.
+    /// `(?u)` and `(?-u)` in the pattern override this.
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.flags.unicode = yes;
+        self
+    }
+
+    /// Whether letters match regardless of case, as `(?i)` does in the pattern.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// Whether to match regardless of case when no character the pattern matches
+    /// literally is uppercase. Letters of escapes such as `\W` or `\p{Greek}` do not count,
+    /// while one an escape stands for, such as `\x41`, does.
+    pub fn smart_case(&mut self, yes: bool) -> &mut Self {
+        self.smart_case = yes;
+        self
+    }
+
+    /// Whether `^` and `$` match at every line start and end, as `(?m)` does.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.flags.multi_line = yes;
+        self
+    }
+
+    /// Whether `.` matches a line feed, as `(?s)` does.
+    pub fn dot_all(&mut self, yes: bool) -> &mut Self {
+        self.flags.dot_all = yes;
+        self
+    }
+
+    /// How many steps a search may take when the pattern needs the backtracking engine,
+    /// because of backreferences, lookarounds or atomic groups, before it gives up with
+    /// [`MatchError::StepLimitExceeded`].
+    pub fn backtrack_limit(&mut self, limit: usize) -> &mut Self {
+        self.backtrack_limit = limit;
+        self
+    }
+
+    pub fn build(&self) -> Result<Regex, PatternError> {
+        let mut parsed = parse(&self.pattern, self.flags)?;
+        if self.smart_case && !self.flags.case_insensitive && !parsed.has_uppercase_literal {
+            parsed = parse(&self.pattern, Flags { case_insensitive: true, ..self.flags })?;
+        }
+        let program = nfa::compile(&parsed.pattern, parsed.group_count)?;
+        Ok(Regex {
+            pattern: parsed.pattern,
+            group_count: parsed.group_count,
+            group_names: parsed.group_names.into(),
+            program,
+            dfa_cache: Mutex::default(),
+            pike_cache: Mutex::default(),
+            backtrack_limit: self.backtrack_limit,
+        })
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.group_count
+    }
+
+    /// # Panics
+    ///
+    /// Panics if backtracking gives up; see [`Regex::try_is_match`].
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.try_is_match(haystack).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Like [`Regex::is_match`], but returns an error when the backtracking engine
+    /// exceeds its step limit; see [`RegexBuilder::backtrack_limit`].
+    pub fn try_is_match(&self, haystack: &str) -> Result<bool, MatchError> {
+        if let (Some(program), Ok(mut cache)) = (&self.program, self.dfa_cache.try_lock()) {
+            if let Some(found) = dfa::is_match(program, &mut cache, haystack) {
+                return Ok(found);
+            }
+        }
+        Ok(self.search_from(haystack, 0)?.is_some())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if backtracking gives up; see [`Regex::try_find`].
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_iter(haystack).next()
+    }
+
+    pub fn try_find<'h>(&self, haystack: &'h str) -> Result<Option<Match<'h>>, MatchError> {
+        let slots = self.search_from(haystack, 0)?;
+        Ok(slots.and_then(|slots| slots[0]).map(|(start, end)| Match { haystack, start, end }))
+    }
+
+    /// # Panics
+    ///
+    /// The iterator panics if backtracking gives up.
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches { regex: self, haystack, position: Some(0) }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if backtracking gives up; see [`Regex::try_captures`].
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        self.try_captures(haystack).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    pub fn try_captures<'h>(&self, haystack: &'h str) -> Result<Option<Captures<'h>>, MatchError> {
+        let Some(slots) = self.search_from(haystack, 0)? else {
+            return Ok(None);
+        };
+        Ok(Some(Captures { haystack, slots, group_names: self.group_names.clone() }))
+    }
+
+    fn search_from(&self, haystack: &str, from: usize) -> Result<Option<Slots>, MatchError> {
+        debug_assert!(haystack.is_char_boundary(from));
+        match &self.program {
+            Some(program) => Ok(match self.pike_cache.try_lock() {
+                Ok(mut cache) => pikevm::search(program, &mut cache, haystack, from),
+                Err(_) => pikevm::search(program, &mut pikevm::Cache::default(), haystack, from),
+            }),
+            None => backtrack::search(&self.pattern, self.group_count, haystack, from, self.backtrack_limit),
+        }
+    }
+}
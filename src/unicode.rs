@@ -0,0 +1,73 @@
+use crate::classes::ClassSet;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PropertyKind {
+    GeneralCategory,
+    Script,
+    Binary,
+}
+
+/// How literals, classes and back references compare characters that differ in case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseFolding {
+    Exact,
+    Ascii,
+    Unicode,
+}
+
+impl CaseFolding {
+    /// Whether `a` and `b` are the same character under this folding.
+    pub fn matches(self, a: char, b: char) -> bool {
+        match self {
+            CaseFolding::Exact => a == b,
+            CaseFolding::Ascii => a.eq_ignore_ascii_case(&b),
+            CaseFolding::Unicode => a == b || simple_case_variants(a).contains(&b),
+        }
+    }
+}
+
+type Property = (&'static str, PropertyKind, &'static [(char, char)]);
+
+include!(concat!(env!("OUT_DIR"), "/unicode_tables.rs"));
+
+/// Applies the loose matching of UTS #18: case, spaces, underscores and hyphens are ignored.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn lookup(name: &str, kind: Option<PropertyKind>) -> Option<&'static [(char, char)]> {
+    let start = PROPERTIES.partition_point(|(n, _, _)| *n < name);
+    PROPERTIES[start..]
+        .iter()
+        .take_while(|(n, _, _)| *n == name)
+        .find(|(_, k, _)| kind.map_or(true, |kind| kind == *k))
+        .map(|(_, _, table)| *table)
+}
+
+/// Resolves the body of a `\p{...}` class: a general category, a script or a binary
+/// property, optionally qualified as `gc=...` or `sc=...`.
+pub fn property_class(name: &str) -> Option<ClassSet> {
+    let (kind, value) = match name.split_once(['=', ':']) {
+        Some((key, value)) => match normalize(key).as_str() {
+            "gc" | "generalcategory" => (Some(PropertyKind::GeneralCategory), value),
+            "sc" | "script" => (Some(PropertyKind::Script), value),
+            _ => return None,
+        },
+        None => (None, name),
+    };
+    let value = normalize(value);
+    let table = lookup(&value, kind).or_else(|| lookup(value.strip_prefix("is")?, kind))?;
+    Some(ClassSet::from_ranges(table))
+}
+
+/// The other characters `c` matches under Unicode simple case folding, such as `k`, `K`
+/// and the Kelvin sign for one another.
+pub fn simple_case_variants(c: char) -> &'static [char] {
+    match CASE_FOLDING.binary_search_by_key(&c, |(c, _)| *c) {
+        Ok(index) => CASE_FOLDING[index].1,
+        Err(_) => &[],
+    }
+}